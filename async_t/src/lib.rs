@@ -1,7 +1,37 @@
-#[cfg(not(feature = "boxed"))]
 pub use async_t_internal::async_trait;
 
 pub use async_t_internal::impl_trait;
 
-#[cfg(feature = "boxed")]
-pub use async_trait::async_trait;
+/// `future!(T)` is `impl Future<Output = T>`, for the return types of callbacks.
+/// within `#[async_trait]` it's also `Send` unless the method is unsend.
+#[macro_export]
+macro_rules! future {
+    ($($output:tt)*) => {
+        impl ::core::future::Future<Output = $($output)*>
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    //! items used by the code generated by `#[async_trait]`.
+
+    /// marks an async method whose future is `Send`.
+    pub struct Send;
+    /// marks an async method whose future isn't `Send`.
+    pub struct Unsend;
+
+    /// marks an async method with a default body, whose overrides need `#[provided]`.
+    pub struct Provided;
+    /// marks an async method without a default body.
+    pub struct Required;
+
+    /// checks that the trait and its implementation agree on the `Send`-ness of a method.
+    #[diagnostic::on_unimplemented(
+        message = "the future of this method is `Send` in only one of the trait and its implementation",
+        label = "doesn't match the trait declaration",
+        note = "add or remove `#[unsend]` or `#[send]` so the trait and the implementation agree"
+    )]
+    pub trait SendPolicy<Declared> {}
+    impl SendPolicy<Send> for Send {}
+    impl SendPolicy<Unsend> for Unsend {}
+}
@@ -0,0 +1,12 @@
+/// the `boxed` and `rpitit` backends work on stable.
+#[test]
+fn stable() {
+    trybuild::TestCases::new().pass("tests/stable/*.rs");
+}
+
+/// the `static` backend requires nightly features, so it's only checked on request.
+#[test]
+#[ignore = "requires nightly, run with `cargo +nightly test -- --include-ignored`"]
+fn nightly() {
+    trybuild::TestCases::new().pass("tests/nightly/*.rs");
+}
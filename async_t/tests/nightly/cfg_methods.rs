@@ -0,0 +1,48 @@
+#![feature(impl_trait_in_assoc_type)]
+
+use async_t::async_trait;
+
+#[async_trait]
+pub trait Platform {
+    #[cfg(unix)]
+    async fn name(&self) -> &'static str;
+
+    #[cfg(not(unix))]
+    async fn name(&self) -> String;
+
+    #[cfg(unix)]
+    async fn separator(&self) -> char {
+        '/'
+    }
+
+    #[cfg(not(unix))]
+    async fn separator(&self) -> char {
+        '\\'
+    }
+}
+
+struct Host;
+
+#[async_trait]
+impl Platform for Host {
+    #[cfg(unix)]
+    async fn name(&self) -> &'static str {
+        "unix"
+    }
+
+    #[cfg(not(unix))]
+    async fn name(&self) -> String {
+        String::from("other")
+    }
+
+    #[cfg(unix)]
+    #[provided]
+    async fn separator(&self) -> char {
+        '/'
+    }
+}
+
+fn main() {
+    let _ = Host.name();
+    let _ = Host.separator();
+}
@@ -0,0 +1,31 @@
+#![feature(impl_trait_in_assoc_type)]
+
+mod runtime {
+    pub use async_t;
+}
+
+use runtime::async_t::async_trait;
+
+#[async_trait(crate = crate::runtime::async_t)]
+pub trait Handler {
+    async fn handle(&self, x: u8) -> u8;
+    fn callback(&self) -> impl Fn(u8) -> crate::runtime::async_t::future!(u8);
+}
+
+struct Echo;
+
+#[async_trait(crate = crate::runtime::async_t)]
+impl Handler for Echo {
+    async fn handle(&self, x: u8) -> u8 {
+        x
+    }
+
+    fn callback(&self) -> impl Fn(u8) -> crate::runtime::async_t::future!(u8) {
+        |x| async move { x }
+    }
+}
+
+fn main() {
+    let _ = Echo.handle(1);
+    let _ = Echo.callback()(1);
+}
@@ -0,0 +1,33 @@
+#![feature(impl_trait_in_assoc_type)]
+
+use async_t::{async_trait, future};
+use std::future::Future;
+use std::rc::Rc;
+
+#[async_trait(?Send)]
+pub trait Middleware {
+    fn local(&self) -> impl Fn(u8) -> future!(Rc<u8>);
+    #[send]
+    fn handler(&self) -> impl Fn(u8) -> future!(u8);
+}
+
+struct Identity;
+
+#[async_trait(?Send)]
+impl Middleware for Identity {
+    fn local(&self) -> impl Fn(u8) -> future!(Rc<u8>) {
+        |x| async move { Rc::new(x) }
+    }
+
+    #[send]
+    fn handler(&self) -> impl Fn(u8) -> future!(u8) {
+        |x| async move { x }
+    }
+}
+
+fn spawn<F: Future + Send>(_: F) {}
+
+fn main() {
+    let _ = Identity.local()(1);
+    spawn(Identity.handler()(1));
+}
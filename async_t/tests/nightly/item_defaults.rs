@@ -0,0 +1,35 @@
+#![feature(impl_trait_in_assoc_type, type_alias_impl_trait, associated_type_defaults)]
+
+use async_t::impl_trait;
+use std::fmt::Display;
+
+#[impl_trait]
+pub trait Items<T> {
+    const NAME: impl Display;
+    const SHOW: impl Fn(u8) -> String = |x| format!("<{}>", x);
+    type Out = Vec<impl Display>;
+
+    fn out(&self) -> Self::Out;
+
+    fn show(&self, x: u8) -> String {
+        (Self::SHOW)(x)
+    }
+}
+
+struct Numbers;
+
+#[impl_trait]
+impl Items<u8> for Numbers {
+    const NAME: impl Display = "numbers";
+    #[provided]
+    type Out = Vec<impl Display>;
+
+    fn out(&self) -> Self::Out {
+        vec![1, 2]
+    }
+}
+
+fn main() {
+    let numbers = Numbers;
+    let _ = (Numbers::NAME.to_string(), numbers.out().len(), numbers.show(1));
+}
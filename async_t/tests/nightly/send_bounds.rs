@@ -0,0 +1,67 @@
+#![feature(impl_trait_in_assoc_type)]
+
+use async_t::async_trait;
+use std::future::Future;
+
+#[async_trait]
+pub trait Cache {
+    async fn get(&self, key: u8) -> u8;
+
+    async fn insert<V: Into<u8>>(&mut self, value: V);
+
+    #[no_send_bounds]
+    async fn len(&self) -> usize;
+
+    async fn twice(&mut self, key: u8) -> u8 {
+        self.get(key).await * 2
+    }
+
+    async fn into_first(self) -> u8
+    where
+        Self: Sized,
+    {
+        self.get(0).await
+    }
+}
+
+struct Single(u8);
+
+#[async_trait]
+impl Cache for Single {
+    async fn get(&self, _key: u8) -> u8 {
+        self.0
+    }
+
+    async fn insert<V: Into<u8>>(&mut self, value: V) {
+        self.0 = value.into();
+    }
+
+    #[no_send_bounds]
+    async fn len(&self) -> usize {
+        1
+    }
+}
+
+async fn generic<T: Cache + Sync>(t: &T) -> u8 {
+    t.get(1).await
+}
+
+async fn companion<T: CacheSend>(t: &T) -> u8 {
+    t.get(1).await
+}
+
+async fn unbounded<T: Cache>(t: &T) -> usize {
+    t.len().await
+}
+
+fn spawn<F: Future + Send>(_: F) {}
+
+fn main() {
+    spawn(generic(&Single(1)));
+    spawn(companion(&Single(1)));
+    let _ = unbounded(&Single(1));
+    let mut single = Single(1);
+    spawn(single.insert(2));
+    spawn(single.twice(1));
+    spawn(Single(1).into_first());
+}
@@ -0,0 +1,33 @@
+#![feature(impl_trait_in_assoc_type)]
+
+use async_t::async_trait;
+
+#[async_trait(static, ?Send)]
+pub trait Store {
+    async fn get(&self, key: &str) -> Option<&u8>;
+    async fn put(&mut self, key: &str, value: u8);
+    async fn nested(&self, key: &&str) -> u8;
+}
+
+struct Single(u8);
+
+#[async_trait(static, ?Send)]
+impl Store for Single {
+    async fn get(&self, _key: &str) -> Option<&u8> {
+        Some(&self.0)
+    }
+
+    async fn put(&mut self, _key: &str, value: u8) {
+        self.0 = value;
+    }
+
+    async fn nested(&self, key: &&str) -> u8 {
+        key.len() as u8
+    }
+}
+
+fn spawnable<T: StoreSend>(_: &T) {}
+
+fn main() {
+    spawnable(&Single(1));
+}
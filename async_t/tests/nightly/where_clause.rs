@@ -0,0 +1,54 @@
+#![feature(impl_trait_in_assoc_type)]
+
+use async_t::async_trait;
+use std::fmt::Display;
+
+#[async_trait]
+pub trait Store {
+    type Item;
+
+    fn put(&mut self, item: Self::Item) -> String
+    where
+        Self::Item: Into<impl Display>;
+
+    async fn show(&self, item: Self::Item) -> String
+    where
+        Self::Item: Into<impl Display + Send>;
+}
+
+struct Items<T>(Vec<T>);
+
+#[async_trait]
+impl<T: Send + Sync> Store for Items<T> {
+    type Item = T;
+
+    fn put(&mut self, item: T) -> String
+    where
+        T: Into<impl Display>,
+    {
+        let shown: Self::impl_trait_put_0 = String::from("item");
+        self.0.push(item);
+        shown.to_string()
+    }
+
+    async fn show(&self, _item: T) -> String
+    where
+        T: Into<impl Display + Send>,
+    {
+        let shown: Self::impl_trait_show_0 = self.0.len();
+        shown.to_string()
+    }
+}
+
+// the hidden types stay opaque, so only generic callers can meet the bounds.
+pub fn put<S: Store>(store: &mut S, item: S::Item) -> String
+where
+    S::Item: Into<S::impl_trait_put_0>,
+{
+    store.put(item)
+}
+
+fn main() {
+    let items = Items(Vec::<u8>::new());
+    let _ = items.0.len();
+}
@@ -0,0 +1,40 @@
+use async_t::async_trait;
+use std::fmt::Display;
+
+#[async_trait(dyn, boxed)]
+pub trait Source {
+    /// what it yields
+    type Item: Display + Send;
+    const SIZE: usize;
+    type Batch<'a>
+    where
+        Self: 'a;
+
+    async fn get(&self) -> Self::Item;
+    async fn all(&self, n: usize) -> Vec<Self::Item> {
+        let mut all = vec![];
+        for _ in 0..n {
+            all.push(self.get().await);
+        }
+        all
+    }
+}
+
+struct Count;
+
+#[async_trait(boxed)]
+impl Source for Count {
+    type Item = u8;
+    const SIZE: usize = 1;
+    type Batch<'a> = &'a [u8];
+
+    async fn get(&self) -> u8 {
+        1
+    }
+}
+
+fn main() {
+    let source: Box<dyn DynSource<Item = u8> + Sync> = Box::new(Count);
+    let _ = source.get();
+    let _ = source.all(2);
+}
@@ -0,0 +1,35 @@
+use async_t::async_trait;
+
+#[async_trait(dyn, boxed)]
+pub trait Sum {
+    async fn sum(&self, items: impl Iterator<Item = &u8> + Send) -> u8;
+    async fn first(&self, items: impl Iterator<Item = &u8> + Send, fallback: &u8) -> &u8;
+    async fn run<T: Into<u8> + Send>(&self, t: T, f: impl FnOnce(u8) -> u8 + Send) -> u8;
+}
+
+struct Offset(u8);
+
+#[async_trait(boxed)]
+impl Sum for Offset {
+    async fn sum(&self, items: impl Iterator<Item = &u8> + Send) -> u8 {
+        items.fold(self.0, |sum, item| sum + item)
+    }
+
+    async fn first(&self, _items: impl Iterator<Item = &u8> + Send, _fallback: &u8) -> &u8 {
+        &self.0
+    }
+
+    async fn run<T: Into<u8> + Send>(&self, t: T, f: impl FnOnce(u8) -> u8 + Send) -> u8 {
+        f(t.into() + self.0)
+    }
+}
+
+fn main() {
+    let items = vec![1, 2];
+    let offset = Offset(1);
+    let _ = Sum::sum(&offset, items.iter());
+    let _ = Sum::first(&offset, items.iter(), &0);
+    // every argument of type `impl Trait` takes an explicit param after those of the method.
+    let _ = Sum::run::<u8, _>(&offset, 1, |x| x);
+    let _: &dyn DynSum = &offset;
+}
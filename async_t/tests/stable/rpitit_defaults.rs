@@ -0,0 +1,46 @@
+use async_t::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+
+#[async_trait(rpitit)]
+trait Job {
+    async fn id(&self) -> u8;
+
+    async fn twice(&mut self) -> u8 {
+        self.id().await * 2
+    }
+
+    async fn into_id(self) -> u8
+    where
+        Self: Sized,
+    {
+        self.id().await
+    }
+
+    async fn boxed(self: Box<Self>) -> u8 {
+        self.id().await
+    }
+
+    async fn shared(self: Arc<Self>) -> u8 {
+        self.id().await + 1
+    }
+}
+
+struct Noop(u8);
+
+#[async_trait(rpitit)]
+impl Job for Noop {
+    async fn id(&self) -> u8 {
+        self.0
+    }
+}
+
+fn spawn<F: Future + Send>(_: F) {}
+
+fn main() {
+    let mut noop = Noop(1);
+    spawn(noop.twice());
+    spawn(Noop(1).into_id());
+    spawn(Box::new(Noop(1)).boxed());
+    spawn(Arc::new(Noop(1)).shared());
+}
@@ -0,0 +1,75 @@
+use async_t::async_trait;
+use std::future::Future;
+
+#[async_trait(boxed)]
+trait Store<'a, T: 'a, const N: usize> {
+    async fn get(&self, key: &'a str) -> Option<[T; N]>;
+    async fn key(&self) -> &'a str;
+    async fn put(&mut self, key: &'a str, value: [T; N]);
+}
+
+struct Memory<'a, T, const N: usize> {
+    key: &'a str,
+    value: [T; N],
+}
+
+#[async_trait(boxed)]
+impl<'a, T: Copy + Send + Sync + 'a, const N: usize> Store<'a, T, N> for Memory<'a, T, N> {
+    async fn get(&self, key: &'a str) -> Option<[T; N]> {
+        (key == self.key).then_some(self.value)
+    }
+
+    async fn key(&self) -> &'a str {
+        self.key
+    }
+
+    async fn put(&mut self, key: &'a str, value: [T; N]) {
+        self.key = key;
+        self.value = value;
+    }
+}
+
+struct Empty;
+
+#[async_trait(boxed)]
+impl<'a> Store<'a, u8, 2> for Empty {
+    async fn get(&self, _key: &'a str) -> Option<[u8; 2]> {
+        None
+    }
+
+    async fn key(&self) -> &'a str {
+        ""
+    }
+
+    async fn put(&mut self, _key: &'a str, _value: [u8; 2]) {}
+}
+
+#[async_trait(dyn, boxed)]
+trait Cache<'a, T: 'a, const N: usize> {
+    async fn get(&self, key: &'a str) -> Option<[T; N]>;
+}
+
+#[async_trait(boxed)]
+impl<'a, T: Copy + Send + Sync + 'a, const N: usize> Cache<'a, T, N> for Memory<'a, T, N> {
+    async fn get(&self, key: &'a str) -> Option<[T; N]> {
+        (key == self.key).then_some(self.value)
+    }
+}
+
+fn assert_send<F: Future + Send>(future: F) -> F {
+    future
+}
+
+fn main() {
+    let mut memory = Memory {
+        key: "a",
+        value: [1u8, 2, 3],
+    };
+    let _ = assert_send(Store::get(&memory, "a"));
+    let _ = Store::put(&mut memory, "b", [4, 5, 6]);
+    let _ = Store::<u8, 2>::key(&Empty);
+    let boxed: &dyn Store<'_, u8, 3> = &memory;
+    let _ = assert_send(boxed.key());
+    let cache: &dyn DynCache<'_, u8, 3> = &memory;
+    let _ = assert_send(cache.get("a"));
+}
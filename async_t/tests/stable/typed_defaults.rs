@@ -0,0 +1,39 @@
+use async_t::async_trait;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+#[async_trait(boxed)]
+trait Job {
+    async fn id(&self) -> u8;
+
+    async fn boxed(self: Box<Self>) -> u8 {
+        self.id().await
+    }
+
+    async fn shared(self: Arc<Self>) -> u8 {
+        self.id().await + 1
+    }
+
+    async fn pinned(self: Pin<&mut Self>) -> u8 {
+        self.id().await + 2
+    }
+}
+
+struct Noop(u8);
+
+#[async_trait(boxed)]
+impl Job for Noop {
+    async fn id(&self) -> u8 {
+        self.0
+    }
+}
+
+fn spawn<F: Future + Send>(_: F) {}
+
+fn main() {
+    spawn(Box::new(Noop(1)).boxed());
+    spawn(Arc::new(Noop(1)).shared());
+    let mut noop = Noop(1);
+    spawn(Pin::new(&mut noop).pinned());
+}
@@ -0,0 +1,140 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Ident, Path, Token};
+
+/// arguments accepted by `#[async_trait(...)]`, separated by commas.
+/// | `dyn` also generates a `Dyn{Trait}` companion usable as a trait object.
+/// | `?Send` makes futures unsend by default, `#[send]` opts single methods back in.
+/// | `static`, `boxed` and `rpitit` select the backend, see [`Backend`].
+/// | `crate = path` is where `async_t` is found, if it isn't a direct dependency under that name.
+#[derive(Default)]
+pub(crate) struct Args {
+    pub(crate) dynamic: bool,
+    pub(crate) unsend: bool,
+    pub(crate) backend: Backend,
+    /// where the backend was selected, if it was given explicitly.
+    pub(crate) backend_span: Option<Span>,
+    /// the path to `async_t` given by `crate = path`.
+    pub(crate) krate: Option<Path>,
+}
+
+/// how async methods are desugared.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backend {
+    /// zero-cost hidden associated types, requires nightly.
+    Static,
+    /// `Pin<Box<dyn Future>>`, object safe.
+    Boxed,
+    /// `impl Future` in return position, which rustc supports on stable.
+    Rpitit,
+}
+
+impl Default for Backend {
+    /// the `boxed` and `rpitit` features change the default for every trait
+    /// that doesn't select a backend itself.
+    fn default() -> Self {
+        if cfg!(feature = "boxed") {
+            Backend::Boxed
+        } else if cfg!(feature = "rpitit") {
+            Backend::Rpitit
+        } else {
+            Backend::Static
+        }
+    }
+}
+
+impl Args {
+    /// whether the future of an async method is `Send`.
+    /// `#[send]` and `#[unsend]` take precedence over the default of the trait or impl.
+    pub(crate) fn is_send(&self, attrs: &[Attribute]) -> bool {
+        if attrs.iter().any(|s| s.path.is_ident("send")) {
+            true
+        } else if attrs.iter().any(|s| s.path.is_ident("unsend")) {
+            false
+        } else {
+            !self.unsend
+        }
+    }
+
+    /// the path to `async_t` in generated code, `::async_t` unless `crate = path` is given.
+    /// it's given `span`, so errors about the items it's part of point there.
+    pub(crate) fn krate(&self, span: Span) -> TokenStream2 {
+        let path = match &self.krate {
+            Some(path) => quote!(#path),
+            None => quote!(::async_t),
+        };
+        path.into_iter()
+            .map(|mut token| {
+                token.set_span(span);
+                token
+            })
+            .collect()
+    }
+
+    /// same as [`Args::is_send`], but also strips `#[send]` and `#[unsend]`.
+    pub(crate) fn take_send(&self, attrs: &mut Vec<Attribute>) -> bool {
+        let send = self.is_send(attrs);
+        attrs.retain(|s| !s.path.is_ident("send") && !s.path.is_ident("unsend"));
+        send
+    }
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Args::default();
+        let mut backend = None;
+        while !input.is_empty() {
+            let lookahead = input.lookahead1();
+            if lookahead.peek(Token![dyn]) {
+                input.parse::<Token![dyn]>()?;
+                args.dynamic = true;
+            } else if lookahead.peek(Token![?]) {
+                input.parse::<Token![?]>()?;
+                let ident = input.parse::<Ident>()?;
+                if ident != "Send" {
+                    return Err(syn::Error::new(ident.span(), "expected `Send`"));
+                }
+                args.unsend = true;
+            } else if lookahead.peek(Token![crate]) {
+                input.parse::<Token![crate]>()?;
+                input.parse::<Token![=]>()?;
+                args.krate = Some(Path::parse_mod_style(input)?);
+            } else if lookahead.peek(Token![static]) || lookahead.peek(Ident) {
+                let selected = if input.peek(Token![static]) {
+                    (input.parse::<Token![static]>()?.span, Backend::Static)
+                } else {
+                    let ident = input.parse::<Ident>()?;
+                    let backend = match ident.to_string().as_str() {
+                        "boxed" => Backend::Boxed,
+                        "rpitit" => Backend::Rpitit,
+                        _ => {
+                            return Err(syn::Error::new(
+                                ident.span(),
+                                "expected one of `dyn`, `?Send`, `crate`, `static`, `boxed` or `rpitit`",
+                            ))
+                        }
+                    };
+                    (ident.span(), backend)
+                };
+                if backend.is_some() {
+                    return Err(syn::Error::new(
+                        selected.0,
+                        "only one backend can be selected",
+                    ));
+                }
+                backend = Some(selected);
+            } else {
+                return Err(lookahead.error());
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if let Some((span, backend)) = backend {
+            args.backend = backend;
+            args.backend_span = Some(span);
+        }
+        Ok(args)
+    }
+}
@@ -0,0 +1,102 @@
+use proc_macro_error::emit_error;
+use quote::quote;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Attribute, ImplItem, ItemImpl, ItemTrait, Path, Signature, TraitItem, Type};
+
+use super::Args;
+
+/// expands `future!(T)` into `impl Future<Output = T>`, which is `Send` unless the method
+/// it's found in is unsend. `async T` can't be used instead, since rustc parses the item
+/// before handing it to the macro.
+pub(crate) fn trait_async_types(inner_trait: &mut ItemTrait, args: &Args) {
+    inner_trait.items.iter_mut().for_each(|item| {
+        let send = match item {
+            TraitItem::Method(method) => args.is_send(&method.attrs),
+            _ => !args.unsend,
+        };
+        let mut types = AsyncTypes {
+            send,
+            krate: args.krate.as_ref(),
+            found: false,
+        };
+        types.visit_trait_item_mut(item);
+        if let TraitItem::Method(method) = item {
+            take_future_send(&mut method.attrs, &method.sig, types.found, args);
+        }
+    });
+}
+
+/// same as [`trait_async_types`], for implementations.
+pub(crate) fn impl_async_types(inner_impl: &mut ItemImpl, args: &Args) {
+    inner_impl.items.iter_mut().for_each(|item| {
+        let send = match item {
+            ImplItem::Method(method) => args.is_send(&method.attrs),
+            _ => !args.unsend,
+        };
+        let mut types = AsyncTypes {
+            send,
+            krate: args.krate.as_ref(),
+            found: false,
+        };
+        types.visit_impl_item_mut(item);
+        if let ImplItem::Method(method) = item {
+            take_future_send(&mut method.attrs, &method.sig, types.found, args);
+        }
+    });
+}
+
+/// `#[send]` and `#[unsend]` on a method that isn't async only apply to its `future!`s,
+/// so they're taken off once these are expanded.
+fn take_future_send(attrs: &mut Vec<Attribute>, sig: &Signature, found: bool, args: &Args) {
+    if found && sig.asyncness.is_none() {
+        crate::diagnostics::method_attributes(attrs, true);
+        args.take_send(attrs);
+    }
+}
+
+struct AsyncTypes<'a> {
+    send: bool,
+    krate: Option<&'a Path>,
+    /// whether any `future!` was expanded.
+    found: bool,
+}
+
+impl VisitMut for AsyncTypes<'_> {
+    /// the outer future is expanded first, since nested ones are still tokens of its macro.
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Macro(mac) = ty {
+            if is_future(&mac.mac.path, self.krate) {
+                let output = &mac.mac.tokens;
+                // `+ Send` is ambiguous in the return type of `Fn` without parentheses.
+                let future = if self.send {
+                    quote!((impl ::core::future::Future<Output = #output> + Send))
+                } else {
+                    quote!(impl ::core::future::Future<Output = #output>)
+                };
+                self.found = true;
+                match syn::parse2(future) {
+                    Ok(future) => *ty = future,
+                    Err(err) => emit_error!(output, "{}", err),
+                }
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+/// `future!`, `async_t::future!` or `future!` under the path given by `crate = path`.
+fn is_future(path: &Path, krate: Option<&Path>) -> bool {
+    let segments = path.segments.iter().map(|s| &s.ident).collect::<Vec<_>>();
+    match segments[..] {
+        [future] => future == "future",
+        [krate, future] if krate == "async_t" => future == "future",
+        [ref prefix @ .., future] if future == "future" => krate.is_some_and(|krate| {
+            krate
+                .segments
+                .iter()
+                .map(|s| &s.ident)
+                .eq(prefix.iter().copied())
+        }),
+        _ => false,
+    }
+}
@@ -0,0 +1,91 @@
+use quote::quote;
+use syn::{
+    Block, FnArg, GenericArgument, ImplItemMethod, Pat, ReturnType, Signature, TraitItemMethod,
+};
+
+use super::{future_lifetime, provided_bounds, Args};
+
+/// rewrites an async trait method to return a boxed future, keeping the trait object safe.
+pub(crate) fn boxed_trait_method(
+    method: &mut TraitItemMethod,
+    args: &Args,
+    trait_types: &[GenericArgument],
+) {
+    let send = args.take_send(&mut method.attrs);
+    boxed_signature(&mut method.sig, send, trait_types);
+    object_safe(&mut method.sig);
+    if let Some(block) = &method.default {
+        method.default = Some(boxed_block(block));
+        provided_bounds(&mut method.sig, send);
+    }
+}
+
+/// rewrites an async method of an implementation to return a boxed future.
+pub(crate) fn boxed_impl_method(
+    method: &mut ImplItemMethod,
+    args: &Args,
+    trait_types: &[GenericArgument],
+) {
+    let send = args.take_send(&mut method.attrs);
+    boxed_signature(&mut method.sig, send, trait_types);
+    object_safe(&mut method.sig);
+    method.block = boxed_block(&method.block);
+    if method.attrs.iter().any(|s| s.path.is_ident("provided")) {
+        provided_bounds(&mut method.sig, send);
+    }
+}
+
+/// makes an async signature return `Pin<Box<dyn Future<Output = T> + Send + 'async_trait>>`.
+/// `Self`, the type and lifetime params of the trait and the type params of the method
+/// must outlive the future.
+pub(crate) fn boxed_signature(sig: &mut Signature, send: bool, trait_types: &[GenericArgument]) {
+    sig.asyncness = None;
+    future_lifetime(sig, true);
+    let send = if send { quote!(+ Send) } else { quote!() };
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    sig.output = syn::parse2(quote!(
+        -> ::core::pin::Pin<::std::boxed::Box<
+            dyn ::core::future::Future<Output = #output> + 'async_trait #send
+        >>
+    ))
+    .unwrap();
+    let where_clause = sig.generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(syn::parse2(quote!(Self: 'async_trait)).unwrap());
+    trait_types.iter().for_each(|ty| {
+        where_clause
+            .predicates
+            .push(syn::parse2(quote!(#ty: 'async_trait)).unwrap());
+    });
+}
+
+/// methods without a receiver or with type params can't be called on a trait object,
+/// so they require `Self: Sized`.
+pub(crate) fn object_safe(sig: &mut Signature) {
+    let receiver = match sig.inputs.first() {
+        Some(FnArg::Receiver(_)) => true,
+        Some(FnArg::Typed(typed)) => matches!(&*typed.pat, Pat::Ident(pat) if pat.ident == "self"),
+        None => false,
+    };
+    if !receiver || sig.generics.type_params().next().is_some() {
+        sig.generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse2(quote!(Self: Sized)).unwrap());
+    }
+}
+
+fn boxed_block(block: &Block) -> Block {
+    syn::parse2(quote! {
+        {
+            ::std::boxed::Box::pin(async move {
+                #block
+            })
+        }
+    })
+    .unwrap()
+}
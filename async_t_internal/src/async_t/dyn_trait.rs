@@ -0,0 +1,250 @@
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use proc_macro_error::emit_error;
+use quote::{format_ident, quote};
+use syn::{
+    FnArg, GenericArgument, GenericParam, Ident, ItemTrait, Pat, TraitItem, TraitItemMethod,
+    TraitItemType, WherePredicate,
+};
+
+use super::{boxed_signature, object_safe, param_bounds, receiver_bounds, Args, Backend, Required};
+
+/// generates an object safe `Dyn{Trait}` companion with boxed-future methods,
+/// along with a blanket implementation for every implementor of the trait.
+/// non-async methods returning `impl Trait` are left out.
+/// associated types are declared by the companion as well, and set to those of the implementor.
+/// associated consts and generic associated types aren't object safe, so they're left out,
+/// and reported where a method of the companion refers to them.
+/// with the `static` backend, what `Self` must implement for the futures to be `Send`
+/// becomes a supertrait, so it also holds for the trait object.
+pub(crate) fn dyn_trait(inner_trait: &ItemTrait, args: &Args) -> TokenStream {
+    let vis = &inner_trait.vis;
+    let ident = &inner_trait.ident;
+    let dyn_ident = format_ident!("Dyn{}", ident);
+    let implementor = format_ident!("__AsyncTImplementor");
+
+    let generics = &inner_trait.generics;
+    let where_clause = &generics.where_clause;
+
+    let trait_types = crate::impl_trait::trait_params(&inner_trait.generics);
+    let mut required = Required::default();
+    let unsafe_items = inner_trait
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TraitItem::Const(item) => Some(&item.ident),
+            TraitItem::Type(item) if !item.generics.params.is_empty() => Some(&item.ident),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let (declarations, implementations): (Vec<_>, Vec<_>) = inner_trait
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            TraitItem::Method(method) => dyn_method(
+                method,
+                &implementor,
+                inner_trait,
+                &trait_types,
+                args,
+                &unsafe_items,
+                &mut required,
+            ),
+            TraitItem::Type(item) if item.generics.params.is_empty() => {
+                Some(dyn_type(item, &implementor, inner_trait))
+            }
+            _ => None,
+        })
+        .unzip();
+
+    let (_, trait_generics, _) = inner_trait.generics.split_for_impl();
+    let supertraits = required.bounds().map(|bounds| quote!(: #bounds));
+    let implementor_bounds = required.bounds().map(|bounds| quote!(+ #bounds));
+    let mut impl_generics = inner_trait.generics.clone();
+    impl_generics.params.push(
+        syn::parse2(quote!(#implementor: #ident #trait_generics + ?Sized #implementor_bounds))
+            .unwrap(),
+    );
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    quote!(
+        #vis trait #dyn_ident #generics #supertraits #where_clause {
+            #(#declarations)*
+        }
+
+        impl #impl_generics #dyn_ident #trait_generics for #implementor #where_clause {
+            #(#implementations)*
+        }
+    )
+    .into()
+}
+
+/// an associated type of the companion, which is that of the implementor.
+fn dyn_type(
+    item: &TraitItemType,
+    implementor: &Ident,
+    inner_trait: &ItemTrait,
+) -> (TokenStream2, TokenStream2) {
+    let ident = &inner_trait.ident;
+    let (_, trait_generics, _) = inner_trait.generics.split_for_impl();
+    let name = &item.ident;
+    let colon = &item.colon_token;
+    let bounds = &item.bounds;
+    let docs = item.attrs.iter().filter(|s| s.path.is_ident("doc"));
+    let cfgs = item.attrs.iter().filter(|s| s.path.is_ident("cfg"));
+    let impl_cfgs = cfgs.clone();
+    (
+        quote!(#(#docs)* #(#cfgs)* type #name #colon #bounds;),
+        quote!(#(#impl_cfgs)* type #name = <#implementor as #ident #trait_generics>::#name;),
+    )
+}
+
+fn dyn_method(
+    method: &TraitItemMethod,
+    implementor: &Ident,
+    inner_trait: &ItemTrait,
+    trait_types: &[GenericArgument],
+    args: &Args,
+    unsafe_items: &[&Ident],
+    required: &mut Required,
+) -> Option<(TokenStream2, TokenStream2)> {
+    let ident = &inner_trait.ident;
+    let (_, trait_generics, _) = inner_trait.generics.split_for_impl();
+    let mut sig = method.sig.clone();
+    let name = sig.ident.clone();
+    crate::impl_trait::desugar_where_clause(&mut sig.generics);
+
+    if let Some(item) = self_item(quote!(#sig), unsafe_items) {
+        emit_error!(
+            item,
+            "`Self::{}` isn't object safe, so it can't be used by the methods of `Dyn{}`",
+            item,
+            ident;
+            help = "only associated types without generic params are carried over to the companion"
+        );
+        return None;
+    }
+
+    // the hidden types of the where clause are chosen by the implementation.
+    let (output, where_clause) = (&sig.output, &sig.generics.where_clause);
+    if (sig.asyncness.is_none() && contains_impl(quote!(#output)))
+        || contains_impl(quote!(#where_clause))
+    {
+        return None;
+    }
+
+    let mut call_args = vec![];
+    sig.inputs
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, arg)| match arg {
+            FnArg::Receiver(_) => call_args.push(quote!(self)),
+            FnArg::Typed(typed) => match &*typed.pat {
+                Pat::Ident(pat) if pat.ident == "self" => call_args.push(quote!(self)),
+                _ => {
+                    let arg = format_ident!("__arg{}", i);
+                    *typed.pat = syn::parse2(quote!(#arg)).unwrap();
+                    call_args.push(quote!(#arg));
+                }
+            },
+        });
+
+    // argument position `impl Trait` can't be given explicitly
+    let turbofish = if sig.inputs.iter().any(|arg| contains_impl(quote!(#arg))) {
+        quote!()
+    } else {
+        let params = sig.generics.params.iter().filter_map(|param| match param {
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                Some(quote!(#ident))
+            }
+            GenericParam::Const(c) => {
+                let ident = &c.ident;
+                Some(quote!(#ident))
+            }
+            GenericParam::Lifetime(_) => None,
+        });
+        quote!(::<#(#params),*>)
+    };
+
+    let call = quote!(<#implementor as #ident #trait_generics>::#name #turbofish(#(#call_args),*));
+    let body = if sig.asyncness.is_some() {
+        let send = args.is_send(&method.attrs);
+        boxed_signature(&mut sig, send, trait_types);
+        if method.default.is_some() {
+            super::provided_bounds(&mut sig, send);
+        } else if args.backend == Backend::Static && sig.generics.type_params().next().is_some() {
+            // the futures of required methods need `Self` to outlive every lifetime of the method,
+            // which the bounds of type params can pin. these methods require `Self: Sized` anyway.
+            let lifetimes = sig
+                .generics
+                .lifetimes()
+                .map(|param| param.lifetime.clone())
+                .collect::<Vec<_>>();
+            let outlives = lifetimes
+                .iter()
+                .map(|lifetime| syn::parse2::<WherePredicate>(quote!(Self: #lifetime)).unwrap());
+            sig.generics.make_where_clause().predicates.extend(outlives);
+        }
+        if method.default.is_none()
+            && send
+            && args.backend == Backend::Static
+            && !method
+                .attrs
+                .iter()
+                .any(|s| s.path.is_ident("no_send_bounds"))
+        {
+            // the blanket implementation can only call methods whose bounds it restates.
+            // those of `Self` become supertraits instead, so `dyn Dyn{Trait}` meets them.
+            let receiver = receiver_bounds(&sig);
+            required.send |= receiver.send;
+            required.sync |= receiver.sync;
+            let params = param_bounds(&sig);
+            sig.generics.make_where_clause().predicates.extend(params);
+        }
+        quote!(::std::boxed::Box::pin(#call))
+    } else {
+        call
+    };
+
+    // after the arguments of async methods are named, which makes them generic.
+    object_safe(&mut sig);
+
+    let docs = method.attrs.iter().filter(|s| s.path.is_ident("doc"));
+    let cfgs = method.attrs.iter().filter(|s| s.path.is_ident("cfg"));
+    let impl_cfgs = cfgs.clone();
+    Some((
+        quote!(#(#docs)* #(#cfgs)* #sig;),
+        quote!(#(#impl_cfgs)* #sig { #body }),
+    ))
+}
+
+/// the first of `items` that `ts` refers to as `Self::{item}`.
+fn self_item(ts: TokenStream2, items: &[&Ident]) -> Option<Ident> {
+    let tokens = ts.into_iter().collect::<Vec<_>>();
+    tokens
+        .iter()
+        .enumerate()
+        .find_map(|(i, tt)| match (tt, &tokens[i + 1..]) {
+            (
+                TokenTree::Ident(this),
+                [TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Ident(item), ..],
+            ) if this == "Self"
+                && a.as_char() == ':'
+                && b.as_char() == ':'
+                && items.contains(&item) =>
+            {
+                Some(item.clone())
+            }
+            (TokenTree::Group(group), _) => self_item(group.stream(), items),
+            _ => None,
+        })
+}
+
+fn contains_impl(ts: TokenStream2) -> bool {
+    ts.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => contains_impl(group.stream()),
+        _ => false,
+    })
+}
@@ -0,0 +1,84 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_quote, Attribute, GenericParam, Generics, Ident, ItemTrait, Signature};
+
+/// names the hidden type of the future of an async method, keeping `#[name]` if it's given.
+/// the name is handed on to `impl_trait` as `#[name]`, so the trait and implementations agree on it.
+pub(crate) fn future_name(attrs: &mut Vec<Attribute>, sig: &Signature) -> Ident {
+    let name = crate::impl_trait::take_name(attrs)
+        .unwrap_or_else(|| format_ident!("impl_trait_{}_future", sig.ident.unraw()));
+    let lit = name.to_string();
+    attrs.push(parse_quote!(#[name = #lit]));
+    name
+}
+
+/// `{Trait}{Method}Future<'async_trait, .., Implementor, ..>`, the future returned by a required
+/// async method of `Implementor`, so generic code can store and bound it by name.
+/// lifetimes come first, as rustc requires, then the types of the trait, the implementor
+/// and the types and consts of the method.
+pub(crate) fn future_alias(
+    inner_trait: &ItemTrait,
+    sig: &Signature,
+    name: &Ident,
+    attrs: &[Attribute],
+) -> TokenStream2 {
+    let trait_ident = &inner_trait.ident;
+    let alias = format_ident!(
+        "{}{}Future",
+        trait_ident.unraw(),
+        camel_case(&sig.ident.unraw().to_string())
+    );
+    // the hidden type also has the generic params `impl_trait` lifts out of the arguments.
+    let mut sig = sig.clone();
+    crate::impl_trait::desugar_arguments(&mut sig);
+
+    let mut generics = Generics::default();
+    generics.params.extend(unbounded(&inner_trait.generics));
+    generics.params.push(parse_quote!(__AsyncTImplementor));
+    generics.params.extend(unbounded(&sig.generics));
+    let (_, trait_args, _) = inner_trait.generics.split_for_impl();
+    let (_, method_args, _) = sig.generics.split_for_impl();
+
+    let vis = &inner_trait.vis;
+    let cfgs = attrs.iter().filter(|s| s.path.is_ident("cfg"));
+    let doc = format!("the future returned by [`{}::{}`].", trait_ident, sig.ident);
+    quote! {
+        #[doc = #doc]
+        #(#cfgs)*
+        #vis type #alias #generics =
+            <__AsyncTImplementor as #trait_ident #trait_args>::#name #method_args;
+    }
+}
+
+/// type aliases don't enforce bounds, so rustc warns about them.
+fn unbounded(generics: &Generics) -> impl Iterator<Item = GenericParam> + '_ {
+    generics.params.iter().map(|param| match param {
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            parse_quote!(#ident)
+        }
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            parse_quote!(#lifetime)
+        }
+        GenericParam::Const(param) => {
+            let (ident, ty) = (&param.ident, &param.ty);
+            parse_quote!(const #ident: #ty)
+        }
+    })
+}
+
+fn camel_case(ident: &str) -> String {
+    ident
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .concat()
+}
@@ -1,55 +1,338 @@
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::ItemTrait;
-
-pub(crate) fn trait_implementation(mut inner_trait: ItemTrait) -> TokenStream {
-    inner_trait.items.iter_mut().for_each(|item| match item {
-        syn::TraitItem::Method(method) => {
-            method.sig.asyncness.take().and_then(|_| {
-                let (index, send) = {
-                    let mut index = None;
-                    if method
-                        .attrs
-                        .iter()
-                        .enumerate()
-                        .filter(|(_, s)| s.path.is_ident(&format_ident!("unsend")))
-                        .any(|(i, _)| {
-                            index = Some(i);
-                            true
-                        })
-                    {
-                        (index, quote!())
-                    } else {
-                        (index, quote!(+ Send))
-                    }
-                };
-                index.and_then(|index| {
-                    method.attrs.remove(index);
-                    Some(())
-                });
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    Attribute, FnArg, GenericArgument, Ident, ItemTrait, Pat, PathArguments, Signature, TraitItem,
+    Type, WherePredicate,
+};
+
+use super::{
+    boxed_trait_method, future_alias, future_lifetime, future_name, rpitit_trait_method,
+    send_trait, Args, Backend,
+};
+
+pub(crate) fn trait_implementation(mut inner_trait: ItemTrait, args: &Args) -> TokenStream {
+    let mut markers = vec![];
+    let mut futures = vec![];
+    let required = self_bounds(&inner_trait, args);
+    let trait_types = crate::impl_trait::trait_params(&inner_trait.generics);
+    inner_trait.items.iter_mut().for_each(|item| {
+        if let syn::TraitItem::Method(method) = item {
+            let is_async = method.sig.asyncness.is_some();
+            crate::diagnostics::method_attributes(&mut method.attrs, is_async);
+            let bounded = !take_no_send_bounds(&mut method.attrs);
+            if is_async && args.backend == Backend::Boxed {
+                boxed_trait_method(method, args, &trait_types);
+            } else if is_async && args.backend == Backend::Rpitit {
+                rpitit_trait_method(method, args);
+            } else if method.sig.asyncness.take().is_some() {
+                // provided methods keep `impl Future` in return position, which only captures
+                // the inputs that outlive the future.
+                let provided = method.default.is_some();
+                future_lifetime(&mut method.sig, provided);
+                let send = args.take_send(&mut method.attrs);
+                let send_bound = if send { quote!(+ Send) } else { quote!() };
+                let outlives = provided.then(|| quote!(+ 'async_trait));
                 let ret = match &method.sig.output {
                     syn::ReturnType::Default => {
-                        quote!(-> impl ::core::future::Future<Output = ()> + 'async_trait #send)
+                        quote!(-> impl ::core::future::Future<Output = ()> #outlives #send_bound)
                     }
                     syn::ReturnType::Type(_, ty) => {
-                        quote!(-> impl ::core::future::Future<Output = #ty> + 'async_trait #send)
+                        quote!(-> impl ::core::future::Future<Output = #ty> #outlives #send_bound)
                     }
                 };
                 method.sig.output = syn::parse2(ret).unwrap();
-                method
-                    .sig
-                    .generics
-                    .params
-                    .push(syn::parse2(quote!('async_trait)).unwrap());
-                method.sig.generics.type_params_mut().for_each(|param| {
-                    param
-                        .bounds
-                        .push(syn::parse2(quote!('async_trait)).unwrap());
-                });
-                Some(())
-            });
+                // the markers are configured along with their method.
+                let cfgs = method
+                    .attrs
+                    .iter()
+                    .filter(|s| s.path.is_ident("cfg"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                // provided methods keep their `impl Future` return in the trait,
+                // so implementors inherit them without restating the method.
+                if let Some(block) = &method.default {
+                    method.default = Some(
+                        syn::parse2(quote! {
+                            {
+                                async move {
+                                    #block
+                                }
+                            }
+                        })
+                        .unwrap(),
+                    );
+                    provided_bounds(&mut method.sig, send);
+                    // default bodies can call any of the required methods.
+                    if let Some(predicate) = required.predicate(quote!(Self)).filter(|_| send) {
+                        method
+                            .sig
+                            .generics
+                            .make_where_clause()
+                            .predicates
+                            .push(predicate);
+                    }
+                    let (marker, kind) = provided_marker(&method.sig, true, args);
+                    markers.push(syn::TraitItem::Verbatim(quote! {
+                        #(#cfgs)*
+                        #[doc(hidden)]
+                        #[allow(non_upper_case_globals)]
+                        const #marker: #kind = #kind;
+                    }));
+                } else {
+                    let (marker, kind) = provided_marker(&method.sig, false, args);
+                    markers.push(syn::TraitItem::Verbatim(quote! {
+                        #(#cfgs)*
+                        #[doc(hidden)]
+                        #[allow(non_upper_case_globals)]
+                        const #marker: #kind;
+                    }));
+                    if send && bounded {
+                        send_bounds(&mut method.sig);
+                    }
+                    let (marker, policy) = send_marker(&method.sig, send, args);
+                    let krate = args.krate(Span::call_site());
+                    markers.push(syn::TraitItem::Verbatim(quote! {
+                        #(#cfgs)*
+                        #[doc(hidden)]
+                        #[allow(non_camel_case_types)]
+                        type #marker: #krate::__private::SendPolicy<#policy>;
+                    }));
+                    let name = future_name(&mut method.attrs, &method.sig);
+                    futures.push((method.sig.clone(), name, method.attrs.clone()));
+                }
+            }
+        }
+    });
+    inner_trait.items.append(&mut markers);
+    let aliases = futures
+        .iter()
+        .map(|(sig, name, attrs)| future_alias(&inner_trait, sig, name, attrs))
+        .collect::<TokenStream2>();
+    let send_trait = (!futures.is_empty()).then(|| send_trait(&inner_trait, &futures, &required));
+    let mut ts = crate::impl_trait::impl_trait(inner_trait, args.backend);
+    ts.extend(TokenStream::from(quote!(#aliases #send_trait)));
+    ts
+}
+
+/// the hidden associated type which records whether the future of a required method is `Send`,
+/// so implementations disagreeing with the trait get an error pointing at the method.
+pub(crate) fn send_marker(sig: &Signature, send: bool, args: &Args) -> (Ident, TokenStream2) {
+    let marker = format_ident!("__async_t_send_{}", sig.ident);
+    let krate = args.krate(sig.ident.span());
+    let policy = if send {
+        quote_spanned!(sig.ident.span()=> #krate::__private::Send)
+    } else {
+        quote_spanned!(sig.ident.span()=> #krate::__private::Unsend)
+    };
+    (marker, policy)
+}
+
+/// the hidden associated const which records whether an async method has a default body,
+/// so an implementation overriding it without `#[provided]`, or giving `#[provided]` to a method
+/// without one, gets an error pointing at the method.
+pub(crate) fn provided_marker(
+    sig: &Signature,
+    provided: bool,
+    args: &Args,
+) -> (Ident, TokenStream2) {
+    let marker = format_ident!("__async_t_provided_{}", sig.ident);
+    let krate = args.krate(sig.ident.span());
+    let kind = if provided {
+        quote_spanned!(sig.ident.span()=> #krate::__private::Provided)
+    } else {
+        quote_spanned!(sig.ident.span()=> #krate::__private::Required)
+    };
+    (marker, kind)
+}
+
+/// a provided method's future borrows or owns `Self`, so it's only `Send`
+/// if `Self` meets the [`receiver_bounds`] of the method.
+/// taking `self` by value in a default body also requires `Self: Sized`,
+/// and owning `Self`, as in `self` or `self: Box<Self>`, requires that it outlives the future,
+/// where the future is bound by `'async_trait`. `impl Future` of the `rpitit` backend captures
+/// every lifetime instead, and has none.
+pub(crate) fn provided_bounds(sig: &mut Signature, send: bool) {
+    let outlived = sig
+        .generics
+        .lifetimes()
+        .any(|param| param.lifetime.ident == "async_trait");
+    let owned = match sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_none() && outlived => {
+            Some(quote!(Self: Sized + 'async_trait))
+        }
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_none() => {
+            Some(quote!(Self: Sized))
+        }
+        Some(FnArg::Typed(arg)) => match &*arg.pat {
+            Pat::Ident(pat) if pat.ident == "self" && outlived && !borrows(quote!(#arg)) => {
+                Some(quote!(Self: 'async_trait))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    let owned = owned.map(|bound| syn::parse2::<WherePredicate>(bound).unwrap());
+    let receiver = receiver_bounds(sig).predicate(quote!(Self));
+    let predicates = owned.into_iter().chain(receiver.filter(|_| send));
+    sig.generics
+        .make_where_clause()
+        .predicates
+        .extend(predicates);
+}
+
+/// whether a type holds a reference, such as `Pin<&mut Self>`.
+fn borrows(ts: TokenStream2) -> bool {
+    ts.into_iter().any(|tt| match tt {
+        TokenTree::Punct(punct) => punct.as_char() == '&',
+        TokenTree::Group(group) => borrows(group.stream()),
+        _ => false,
+    })
+}
+
+/// takes `#[no_send_bounds]` off a method, which leaves out the bounds of [`send_bounds`].
+pub(crate) fn take_no_send_bounds(attrs: &mut Vec<Attribute>) -> bool {
+    let found = attrs.iter().any(|s| s.path.is_ident("no_send_bounds"));
+    attrs.retain(|s| !s.path.is_ident("no_send_bounds"));
+    found
+}
+
+/// what the send bounds of the required async methods of a trait ask of `Self`,
+/// which every implementor has to meet.
+/// methods behind `#[cfg]` are left out, since a single bound can't be configured out.
+pub(crate) fn self_bounds(inner_trait: &ItemTrait, args: &Args) -> Required {
+    let mut required = Required::default();
+    if args.backend != Backend::Static {
+        return required;
+    }
+    inner_trait.items.iter().for_each(|item| match item {
+        TraitItem::Method(method)
+            if method.sig.asyncness.is_some()
+                && method.default.is_none()
+                && args.is_send(&method.attrs)
+                && !method
+                    .attrs
+                    .iter()
+                    .any(|s| s.path.is_ident("no_send_bounds") || s.path.is_ident("cfg")) =>
+        {
+            let receiver = receiver_bounds(&method.sig);
+            required.send |= receiver.send;
+            required.sync |= receiver.sync;
         }
         _ => (),
     });
-    crate::impl_trait::impl_trait(inner_trait)
+    required
+}
+
+/// the future of a required method owns its arguments, so it's only `Send` if `Self` and the
+/// type params of the method are `Sync` behind shared references and `Send` otherwise.
+/// these bounds end up on the hidden type of the future as well, so an implementor that can't
+/// meet them is reported at the implementation instead of wherever its future is spawned.
+pub(crate) fn send_bounds(sig: &mut Signature) {
+    let receiver = receiver_bounds(sig).predicate(quote!(Self));
+    let params = param_bounds(sig);
+    sig.generics
+        .make_where_clause()
+        .predicates
+        .extend(receiver.into_iter().chain(params));
+}
+
+/// what `Self` must implement for the receiver of a method to be `Send`.
+pub(crate) fn receiver_bounds(sig: &Signature) -> Required {
+    let mut required = Required::default();
+    match sig.inputs.first() {
+        Some(FnArg::Receiver(arg)) => match (&arg.reference, &arg.mutability) {
+            (Some(_), None) => required.sync = true,
+            _ => required.send = true,
+        },
+        Some(FnArg::Typed(arg)) => match &*arg.pat {
+            Pat::Ident(pat) if pat.ident == "self" => required.visit(&arg.ty, "Self", false),
+            _ => (),
+        },
+        None => (),
+    }
+    required
+}
+
+/// the bounds the type params of a method need for its arguments to be `Send`.
+pub(crate) fn param_bounds(sig: &Signature) -> Vec<WherePredicate> {
+    sig.generics
+        .type_params()
+        .filter_map(|param| {
+            let ident = &param.ident;
+            let mut required = Required::default();
+            sig.inputs.iter().for_each(|arg| {
+                if let FnArg::Typed(arg) = arg {
+                    required.visit(&arg.ty, &ident.to_string(), false);
+                }
+            });
+            required.predicate(quote!(#ident))
+        })
+        .collect()
+}
+
+/// the auto traits a type must implement for an argument holding it to be `Send`.
+#[derive(Default)]
+pub(crate) struct Required {
+    pub(crate) send: bool,
+    pub(crate) sync: bool,
+}
+
+impl Required {
+    /// `shared` is whether `ty` is behind a shared reference, which is only `Send` if it's `Sync`.
+    /// `Arc<T>` is only `Send` if `T` is both.
+    fn visit(&mut self, ty: &Type, ident: &str, shared: bool) {
+        match ty {
+            Type::Reference(reference) => {
+                self.visit(&reference.elem, ident, reference.mutability.is_none())
+            }
+            Type::Path(path) if path.qself.is_none() && path.path.is_ident(ident) => {
+                if shared {
+                    self.sync = true;
+                } else {
+                    self.send = true;
+                }
+            }
+            Type::Path(path) if path.qself.is_none() => {
+                for segment in &path.path.segments {
+                    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                        for arg in &args.args {
+                            if let GenericArgument::Type(ty) = arg {
+                                if segment.ident == "Arc" {
+                                    self.visit(ty, ident, false);
+                                    self.visit(ty, ident, true);
+                                } else {
+                                    self.visit(ty, ident, shared);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Type::Array(array) => self.visit(&array.elem, ident, shared),
+            Type::Slice(slice) => self.visit(&slice.elem, ident, shared),
+            Type::Tuple(tuple) => tuple
+                .elems
+                .iter()
+                .for_each(|ty| self.visit(ty, ident, shared)),
+            Type::Paren(paren) => self.visit(&paren.elem, ident, shared),
+            Type::Group(group) => self.visit(&group.elem, ident, shared),
+            _ => (),
+        }
+    }
+
+    /// `Send + Sync`, or whichever of them is required.
+    pub(crate) fn bounds(&self) -> Option<TokenStream2> {
+        match (self.send, self.sync) {
+            (true, true) => Some(quote!(Send + Sync)),
+            (true, false) => Some(quote!(Send)),
+            (false, true) => Some(quote!(Sync)),
+            (false, false) => None,
+        }
+    }
+
+    fn predicate(&self, ty: TokenStream2) -> Option<WherePredicate> {
+        let bounds = self.bounds()?;
+        Some(syn::parse2(quote!(#ty: #bounds)).unwrap())
+    }
 }
@@ -2,46 +2,44 @@ use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{ImplItem, ItemImpl};
 
-pub(crate) fn implementation(mut inner_trait: ItemImpl) -> TokenStream {
-    inner_trait.items.iter_mut().for_each(|item| match item {
-        ImplItem::Method(method) => {
-            method.sig.asyncness.take().and_then(|_| {
-                let (index, send) = {
-                    let mut index = None;
-                    if method
-                        .attrs
-                        .iter()
-                        .enumerate()
-                        .filter(|(_, s)| s.path.is_ident(&format_ident!("unsend")))
-                        .any(|(i, _)| {
-                            index = Some(i);
-                            true
-                        })
-                    {
-                        (index, quote!())
-                    } else {
-                        (index, quote!(+ Send))
-                    }
-                };
-                index.and_then(|index| {
-                    method.attrs.remove(index);
-                    Some(())
-                });
+use super::{
+    boxed_impl_method, future_lifetime, future_name, provided_bounds, provided_marker,
+    rpitit_impl_method, send_bounds, send_marker, take_no_send_bounds, Args, Backend,
+};
+
+pub(crate) fn implementation(mut inner_trait: ItemImpl, args: &Args) -> TokenStream {
+    let mut markers = vec![];
+    let trait_types = crate::impl_trait::trait_args(&inner_trait);
+    // inherent implementations have no trait to agree with on `Send` or on future names.
+    let inherent = inner_trait.trait_.is_none();
+    inner_trait.items.iter_mut().for_each(|item| {
+        if let ImplItem::Method(method) = item {
+            let is_async = method.sig.asyncness.is_some();
+            crate::diagnostics::method_attributes(&mut method.attrs, is_async);
+            let bounded = !take_no_send_bounds(&mut method.attrs);
+            if is_async && args.backend == Backend::Boxed {
+                boxed_impl_method(method, args, &trait_types);
+            } else if is_async && args.backend == Backend::Rpitit {
+                rpitit_impl_method(method, args);
+            } else if method.sig.asyncness.take().is_some() {
+                let provided = method
+                    .attrs
+                    .iter()
+                    .any(|s| s.path.is_ident(&format_ident!("provided")));
+                future_lifetime(&mut method.sig, provided);
+                let send = args.take_send(&mut method.attrs);
+                let send_bound = if send { quote!(+ Send) } else { quote!() };
+                let outlives = provided.then(|| quote!(+ 'async_trait));
 
                 let ret = match &method.sig.output {
                     syn::ReturnType::Default => {
-                        quote!(-> impl ::core::future::Future<Output = ()> + 'async_trait #send)
+                        quote!(-> impl ::core::future::Future<Output = ()> #outlives #send_bound)
                     }
                     syn::ReturnType::Type(_, ty) => {
-                        quote!(-> impl ::core::future::Future<Output = #ty> + 'async_trait #send)
+                        quote!(-> impl ::core::future::Future<Output = #ty> #outlives #send_bound)
                     }
                 };
                 method.sig.output = syn::parse2(ret).unwrap();
-                method
-                    .sig
-                    .generics
-                    .params
-                    .push(syn::parse2(quote!('async_trait)).unwrap());
                 let block = &method.block;
                 method.block = syn::parse2(quote! {
                     {
@@ -51,15 +49,36 @@ pub(crate) fn implementation(mut inner_trait: ItemImpl) -> TokenStream {
                     }
                 })
                 .unwrap();
-                method.sig.generics.type_params_mut().for_each(|param| {
-                    param
-                        .bounds
-                        .push(syn::parse2(quote!('async_trait)).unwrap());
-                });
-                Some(())
-            });
+                // overrides of provided methods must restate the bounds of the trait declaration.
+                // `#[provided]` itself is left for `impl_trait` to pick up.
+                // the markers are configured along with their method.
+                let cfgs = method
+                    .attrs
+                    .iter()
+                    .filter(|s| s.path.is_ident("cfg"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !inherent {
+                    let (marker, kind) = provided_marker(&method.sig, provided, args);
+                    markers.push(ImplItem::Verbatim(
+                        quote!(#(#cfgs)* const #marker: #kind = #kind;),
+                    ));
+                }
+                if provided {
+                    provided_bounds(&mut method.sig, send);
+                } else if !inherent {
+                    if send && bounded {
+                        send_bounds(&mut method.sig);
+                    }
+                    let (marker, policy) = send_marker(&method.sig, send, args);
+                    markers.push(ImplItem::Verbatim(
+                        quote!(#(#cfgs)* type #marker = #policy;),
+                    ));
+                    future_name(&mut method.attrs, &method.sig);
+                }
+            }
         }
-        _ => (),
     });
-    crate::impl_trait::trait_implementation(inner_trait)
+    inner_trait.items.append(&mut markers);
+    crate::impl_trait::trait_implementation(inner_trait, args.backend)
 }
@@ -0,0 +1,127 @@
+use proc_macro2::Span;
+use quote::quote;
+use syn::visit_mut::VisitMut;
+use syn::{
+    FnArg, GenericParam, Ident, Lifetime, LifetimeDef, ReturnType, Signature, TypePath,
+    WherePredicate,
+};
+
+use crate::impl_trait::{name_arguments, self_lifetime, Elide, Elision, ResolveElided};
+
+/// adds the `'async_trait` lifetime of the future, which is the lifetime of the receiver.
+/// elided input lifetimes become `'life0`, `'life1` and so on, except for the one of the receiver,
+/// which becomes `'async_trait` itself.
+/// with `outlives`, every input must outlive `'async_trait`, which futures bound by it need,
+/// as they only capture what outlives them.
+/// hidden future types capture every input anyway, and are left unbounded, as rustc can't prove
+/// such bounds for every lifetime at once, which `{Trait}Send` requires.
+/// elided output lifetimes are resolved like rustc would, as they end up in the `Output` of the
+/// future, where nothing is elided.
+/// argument position `impl Trait` is named first, so the future can capture it.
+pub(crate) fn future_lifetime(sig: &mut Signature, outlives: bool) {
+    let named = sig.generics.params.len();
+    name_arguments(sig);
+    let async_trait: Lifetime = syn::parse2(quote!('async_trait)).unwrap();
+    let mut elided = Elision::new(Elided {
+        params: sig
+            .generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect(),
+        lifetimes: vec![],
+        inputs: vec![],
+        implied: vec![],
+    });
+    sig.inputs.iter_mut().for_each(|arg| match arg {
+        FnArg::Receiver(receiver) => {
+            if let Some((_, lt)) = &mut receiver.reference {
+                let lt = lt.get_or_insert_with(|| async_trait.clone());
+                elided.elide.inputs.push(lt.clone());
+            }
+        }
+        FnArg::Typed(arg) => elided.visit_type_mut(&mut arg.ty),
+    });
+    // the bounds of named arguments are named as well, but rustc doesn't elide outputs from them.
+    let inputs = elided.elide.inputs.len();
+    sig.generics
+        .params
+        .iter_mut()
+        .skip(named)
+        .for_each(|param| elided.visit_generic_param_mut(param));
+    let mut elided = elided.elide;
+    elided.inputs.truncate(inputs);
+
+    // the receiver lends its lifetime to the output, otherwise a single input lifetime does.
+    let output = match self_lifetime(sig) {
+        Some(lifetime) => lifetime.clone(),
+        None if elided.inputs.len() == 1 => elided.inputs.first().cloned(),
+        None => None,
+    };
+    if let (ReturnType::Type(_, ty), Some(lifetime)) = (&mut sig.output, output) {
+        Elision::new(ResolveElided::new(lifetime)).visit_type_mut(ty);
+    }
+
+    sig.generics
+        .params
+        .push(GenericParam::Lifetime(LifetimeDef::new(
+            async_trait.clone(),
+        )));
+    sig.generics.params.extend(
+        elided
+            .lifetimes
+            .into_iter()
+            .map(|lifetime| GenericParam::Lifetime(LifetimeDef::new(lifetime))),
+    );
+    if outlives {
+        sig.generics
+            .lifetimes_mut()
+            .filter(|param| param.lifetime != async_trait)
+            .for_each(|param| param.bounds.push(async_trait.clone()));
+        sig.generics.type_params_mut().for_each(|param| {
+            param
+                .bounds
+                .push(syn::parse2(quote!('async_trait)).unwrap());
+        });
+    }
+    // hidden types are required to restate the bounds implied by the inputs.
+    if !elided.implied.is_empty() {
+        sig.generics
+            .make_where_clause()
+            .predicates
+            .extend(elided.implied);
+    }
+}
+
+/// names every elided lifetime of the inputs, and records every lifetime for output elision.
+struct Elided {
+    /// the type params of the method, which also outlive the references they're behind.
+    params: Vec<Ident>,
+    lifetimes: Vec<Lifetime>,
+    inputs: Vec<Lifetime>,
+    implied: Vec<WherePredicate>,
+}
+
+impl Elide for Elided {
+    fn elided(&mut self) -> Lifetime {
+        let lifetime = Lifetime::new(&format!("'life{}", self.lifetimes.len()), Span::call_site());
+        self.lifetimes.push(lifetime.clone());
+        lifetime
+    }
+
+    fn lifetime(&mut self, lifetime: &Lifetime, references: &[Lifetime]) {
+        self.inputs.push(lifetime.clone());
+        for reference in references {
+            self.implied
+                .push(syn::parse2(quote!(#lifetime: #reference)).unwrap());
+        }
+    }
+
+    fn type_path(&mut self, path: &TypePath, references: &[Lifetime]) {
+        if let Some(param) = self.params.iter().find(|param| path.path.is_ident(*param)) {
+            for reference in references {
+                self.implied
+                    .push(syn::parse2(quote!(#param: #reference)).unwrap());
+            }
+        }
+    }
+}
@@ -1,5 +1,21 @@
+mod args;
+mod async_type;
+mod boxed;
+mod dyn_trait;
+mod future_alias;
 mod impl_trait;
 mod implementation;
+mod lifetimes;
+mod rpitit;
+mod send_trait;
 
+pub(crate) use args::*;
+pub(crate) use async_type::*;
+pub(crate) use boxed::*;
+pub(crate) use dyn_trait::*;
+pub(crate) use future_alias::*;
 pub(crate) use impl_trait::*;
 pub(crate) use implementation::*;
+pub(crate) use lifetimes::*;
+pub(crate) use rpitit::*;
+pub(crate) use send_trait::*;
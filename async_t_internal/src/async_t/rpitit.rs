@@ -0,0 +1,42 @@
+use quote::quote;
+use syn::{ImplItemMethod, ReturnType, TraitItemMethod};
+
+use super::{provided_bounds, Args};
+
+/// rewrites an async trait method to return `impl Future`, which captures every
+/// lifetime and type param of the method, so no extra lifetime is needed.
+pub(crate) fn rpitit_trait_method(method: &mut TraitItemMethod, args: &Args) {
+    let send = args.take_send(&mut method.attrs);
+    let send_bound = if send { quote!(+ Send) } else { quote!() };
+    method.sig.asyncness = None;
+    let output = match &method.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    method.sig.output = syn::parse2(quote!(
+        -> impl ::core::future::Future<Output = #output> #send_bound
+    ))
+    .unwrap();
+    if let Some(block) = &method.default {
+        method.default = Some(
+            syn::parse2(quote! {
+                {
+                    async move {
+                        #block
+                    }
+                }
+            })
+            .unwrap(),
+        );
+        provided_bounds(&mut method.sig, send);
+    }
+}
+
+/// implementations keep their `async fn`, which rustc checks against the `impl Future`
+/// of the trait, including whether it's `Send`.
+pub(crate) fn rpitit_impl_method(method: &mut ImplItemMethod, args: &Args) {
+    let send = args.take_send(&mut method.attrs);
+    if method.attrs.iter().any(|s| s.path.is_ident("provided")) {
+        provided_bounds(&mut method.sig, send);
+    }
+}
@@ -0,0 +1,96 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_quote, Attribute, GenericParam, Ident, ItemTrait, Signature, WherePredicate};
+
+use super::Required;
+
+/// `{Trait}Send`, implemented for every implementor of the trait whose futures are all `Send`,
+/// so generic callers can require `Send` even where the trait is `#[unsend]`.
+/// what the send bounds of its methods require of `Self` becomes a supertrait as well,
+/// so `T: {Trait}Send` can call them without restating it.
+/// the futures are bound as associated types of supertraits, which rustc carries over to
+/// every `T: {Trait}Send`, unlike a where clause.
+/// methods with type or const params are left out, since they can't be bound for every param,
+/// as are methods behind `#[cfg]`, since a single bound can't be configured out.
+/// so are methods whose lifetimes outlive one another, such as `&'a &'b T`, as rustc can't prove
+/// a bound for every lifetime where the hidden type requires such a relation.
+pub(crate) fn send_trait(
+    inner_trait: &ItemTrait,
+    futures: &[(Signature, Ident, Vec<Attribute>)],
+    required: &Required,
+) -> TokenStream2 {
+    let trait_ident = &inner_trait.ident;
+    let send_ident = format_ident!("{}Send", trait_ident.unraw());
+    let trait_args = inner_trait
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                quote!(#ident)
+            }
+            GenericParam::Lifetime(param) => {
+                let lifetime = &param.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Const(param) => {
+                let ident = &param.ident;
+                quote!(#ident)
+            }
+        })
+        .collect::<Vec<_>>();
+    let bounds = futures
+        .iter()
+        .filter(|(sig, _, attrs)| {
+            sig.generics.lifetimes().count() == sig.generics.params.len()
+                && !related(sig)
+                && !attrs.iter().any(|s| s.path.is_ident("cfg"))
+        })
+        .map(|(sig, name, _)| {
+            let lifetimes = sig
+                .generics
+                .lifetimes()
+                .map(|s| &s.lifetime)
+                .collect::<Vec<_>>();
+            quote!(+ for<#(#lifetimes),*> #trait_ident<#(#trait_args,)* #name<#(#lifetimes),*>: Send>)
+        })
+        .collect::<TokenStream2>();
+    let bounds = match required.bounds() {
+        Some(required) => quote!(#bounds + #required),
+        None => bounds,
+    };
+
+    let (_, ty_generics, where_clause) = inner_trait.generics.split_for_impl();
+    let mut generics = inner_trait.generics.clone();
+    generics.params.push(parse_quote!(
+        __AsyncTImplementor: ?Sized + #trait_ident #ty_generics #bounds
+    ));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let trait_generics = &inner_trait.generics;
+    let vis = &inner_trait.vis;
+    let doc = format!(
+        "[`{}`] with every future of its async methods being `Send`.",
+        trait_ident
+    );
+    quote! {
+        #[doc = #doc]
+        #vis trait #send_ident #trait_generics: #trait_ident #ty_generics #bounds #where_clause {}
+
+        impl #impl_generics #send_ident #ty_generics for __AsyncTImplementor #where_clause {}
+    }
+}
+
+/// whether the lifetimes of a method are bound by one another.
+fn related(sig: &Signature) -> bool {
+    sig.generics
+        .lifetimes()
+        .any(|param| !param.bounds.is_empty())
+        || sig.generics.where_clause.iter().any(|where_clause| {
+            where_clause
+                .predicates
+                .iter()
+                .any(|predicate| matches!(predicate, WherePredicate::Lifetime(_)))
+        })
+}
@@ -0,0 +1,130 @@
+use proc_macro::TokenStream;
+use proc_macro_error::{abort, abort_call_site, emit_error};
+
+use crate::async_t::{Args, Backend};
+use syn::spanned::Spanned;
+use syn::{Attribute, Item, ItemFn, ItemImpl, ItemTrait};
+
+/// the items `#[async_trait]` and `#[impl_trait]` can be applied to.
+/// functions are only accepted by `#[impl_trait]`.
+pub(crate) enum Target {
+    Trait(ItemTrait),
+    Impl(ItemImpl),
+    Fn(ItemFn),
+}
+
+/// parses the item a macro was applied to, aborting with a spanned error
+/// naming the kind of item that was found if it isn't supported.
+pub(crate) fn parse_target(name: &str, tokens: TokenStream, functions: bool) -> Target {
+    let item = match syn::parse::<Item>(tokens) {
+        Ok(item) => item,
+        Err(err) => abort!(err.span(), "{}", err),
+    };
+    match item {
+        Item::Trait(inner_trait) => Target::Trait(inner_trait),
+        Item::Impl(inner_impl) => Target::Impl(inner_impl),
+        Item::Fn(inner_fn) if functions => Target::Fn(inner_fn),
+        item => abort!(
+            item.span(),
+            "#[{}] must be applied to {}, found {}",
+            name,
+            if functions {
+                "a trait, an impl block or a function"
+            } else {
+                "a trait or an impl block"
+            },
+            describe(&item)
+        ),
+    }
+}
+
+fn describe(item: &Item) -> &'static str {
+    match item {
+        Item::Const(_) => "a constant",
+        Item::Enum(_) => "an enum",
+        Item::ExternCrate(_) => "an extern crate",
+        Item::Fn(_) => "a function",
+        Item::ForeignMod(_) => "an extern block",
+        Item::Macro(_) | Item::Macro2(_) => "a macro",
+        Item::Mod(_) => "a module",
+        Item::Static(_) => "a static",
+        Item::Struct(_) => "a struct",
+        Item::TraitAlias(_) => "a trait alias",
+        Item::Type(_) => "a type alias",
+        Item::Union(_) => "a union",
+        Item::Use(_) => "a use declaration",
+        _ => "an unsupported item",
+    }
+}
+
+/// the backend of `#[impl_trait]`, which only accepts `static` and `rpitit`.
+/// the `boxed` feature doesn't apply to it, since it has no futures to box.
+pub(crate) fn impl_trait_backend(args: &Args) -> Backend {
+    if args.dynamic || args.unsend {
+        abort_call_site!("#[impl_trait] only accepts `static` or `rpitit`");
+    }
+    match (args.backend, args.backend_span) {
+        (Backend::Boxed, Some(span)) => {
+            abort!(span, "#[impl_trait] only accepts `static` or `rpitit`")
+        }
+        (Backend::Boxed, None) if cfg!(feature = "rpitit") => Backend::Rpitit,
+        (Backend::Boxed, None) => Backend::Static,
+        (backend, _) => backend,
+    }
+}
+
+/// reports an item which isn't a method, constant, type or macro, without stopping the expansion.
+pub(crate) fn unsupported_item(item: &impl Spanned) {
+    emit_error!(
+        item.span(),
+        "unsupported item";
+        help = "supported items are methods, associated constants, associated types and macros"
+    );
+}
+
+/// reports `#[name]` on a method that keeps `impl Trait` in return position,
+/// such as provided methods and every method of the rpitit backend.
+pub(crate) fn unlifted_name(attrs: &mut Vec<Attribute>) {
+    if let Some(name) = crate::impl_trait::take_name(attrs) {
+        emit_error!(
+            name.span(),
+            "`#[name]` only applies to methods whose return type is lifted into a hidden type";
+            help = "provided methods and the `rpitit` backend keep `impl Trait` in return position"
+        );
+    }
+}
+
+/// reports `#[send]`, `#[unsend]` and `#[no_send_bounds]` on a method that isn't async,
+/// or given together on the same method.
+/// `#[send]` and `#[unsend]` are taken off methods using `future!` before.
+/// misplaced attributes are stripped so rustc doesn't report them a second time.
+pub(crate) fn method_attributes(attrs: &mut Vec<Attribute>, is_async: bool) {
+    let find = |name| attrs.iter().find(|s| s.path.is_ident(name));
+    if is_async {
+        if let (Some(_), Some(unsend)) = (find("send"), find("unsend")) {
+            emit_error!(
+                unsend.span(),
+                "a method can't be both `#[send]` and `#[unsend]`"
+            );
+        }
+        return;
+    }
+    ["send", "unsend", "no_send_bounds"]
+        .into_iter()
+        .filter_map(find)
+        .for_each(|attr| {
+            let ident = attr.path.get_ident().unwrap();
+            if ident == "no_send_bounds" {
+                emit_error!(attr.span(), "`#[{}]` only applies to async methods", ident)
+            } else {
+                emit_error!(
+                    attr.span(),
+                    "`#[{}]` only applies to async methods and methods using `future!`",
+                    ident
+                )
+            }
+        });
+    attrs.retain(|s| {
+        !s.path.is_ident("send") && !s.path.is_ident("unsend") && !s.path.is_ident("no_send_bounds")
+    });
+}
@@ -0,0 +1,117 @@
+use quote::{format_ident, quote};
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    Binding, Constraint, FnArg, GenericArgument, GenericParam, Generics,
+    ParenthesizedGenericArguments, QSelf, Signature, Type, TypeBareFn, TypeTraitObject,
+};
+
+/// rustc rejects `impl Trait` in the inputs and output of `Fn` bounds and function pointers,
+/// such as `cb: impl Fn() -> impl Display`.
+/// in argument position these are universal, so they become generic params of the method,
+/// i.e. `fn f<__ImplTrait0: Display>(cb: impl Fn() -> __ImplTrait0)`.
+/// associated type bindings of the where clause are desugared as well.
+pub(crate) fn desugar_arguments(sig: &mut Signature) {
+    lift_arguments(sig, false);
+    desugar_where_clause(&mut sig.generics);
+}
+
+/// desugars the associated type bindings of a where clause,
+/// i.e. `I: IntoIterator<Item = impl Display>` becomes `I: IntoIterator<Item: Display>`.
+pub(crate) fn desugar_where_clause(generics: &mut Generics) {
+    if let Some(where_clause) = &mut generics.where_clause {
+        AssociatedBounds.visit_where_clause_mut(where_clause);
+    }
+}
+
+/// the future of an async method captures its arguments, and has to name their types,
+/// so every `impl Trait` of the arguments becomes a generic param,
+/// i.e. `async fn f(cb: impl Fn())` becomes `async fn f<__ImplTrait0: Fn()>(cb: __ImplTrait0)`.
+pub(crate) fn name_arguments(sig: &mut Signature) {
+    lift_arguments(sig, true);
+}
+
+fn lift_arguments(sig: &mut Signature, nested: bool) {
+    let mut lift = LiftArguments {
+        nested,
+        lifted: vec![],
+    };
+    sig.inputs.iter_mut().for_each(|arg| {
+        if let FnArg::Typed(arg) = arg {
+            lift.visit_type_mut(&mut arg.ty)
+        }
+    });
+    sig.generics.params.extend(lift.lifted);
+}
+
+/// lifts every `impl Trait` which is `nested`, i.e. where rustc rejects it, into a generic param.
+struct LiftArguments {
+    nested: bool,
+    lifted: Vec<GenericParam>,
+}
+
+impl LiftArguments {
+    fn nested(&mut self, visit: impl FnOnce(&mut Self)) {
+        let nested = std::mem::replace(&mut self.nested, true);
+        visit(self);
+        self.nested = nested;
+    }
+}
+
+impl VisitMut for LiftArguments {
+    /// nested `impl Trait` is lifted first, so the outer bounds can name the inner params.
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        visit_mut::visit_type_mut(self, ty);
+        if let Type::ImplTrait(tr) = ty {
+            if self.nested {
+                let ident = format_ident!("__ImplTrait{}", self.lifted.len());
+                let bounds = &tr.bounds;
+                self.lifted
+                    .push(syn::parse2(quote!(#ident: #bounds)).unwrap());
+                *ty = syn::parse2(quote!(#ident)).unwrap();
+            }
+        }
+    }
+
+    fn visit_type_bare_fn_mut(&mut self, func: &mut TypeBareFn) {
+        self.nested(|lift| visit_mut::visit_type_bare_fn_mut(lift, func));
+    }
+
+    fn visit_parenthesized_generic_arguments_mut(
+        &mut self,
+        args: &mut ParenthesizedGenericArguments,
+    ) {
+        self.nested(|lift| visit_mut::visit_parenthesized_generic_arguments_mut(lift, args));
+    }
+
+    // rustc rejects `impl Trait` anywhere in the bounds of a trait object.
+    fn visit_type_trait_object_mut(&mut self, tr: &mut TypeTraitObject) {
+        self.nested(|lift| visit_mut::visit_type_trait_object_mut(lift, tr));
+    }
+
+    // rustc rejects `impl Trait` as the self type of a qualified path.
+    fn visit_qself_mut(&mut self, qself: &mut QSelf) {
+        self.nested(|lift| visit_mut::visit_qself_mut(lift, qself));
+    }
+}
+
+/// `Trait<Assoc = impl Bounds>` in a where clause becomes `Trait<Assoc: Bounds>`,
+/// which callers can prove, unlike a bound on a hidden type.
+struct AssociatedBounds;
+
+impl VisitMut for AssociatedBounds {
+    fn visit_generic_argument_mut(&mut self, arg: &mut GenericArgument) {
+        visit_mut::visit_generic_argument_mut(self, arg);
+        if let GenericArgument::Binding(Binding {
+            ident,
+            ty: Type::ImplTrait(ty),
+            ..
+        }) = arg
+        {
+            *arg = GenericArgument::Constraint(Constraint {
+                ident: ident.clone(),
+                colon_token: Default::default(),
+                bounds: ty.bounds.clone(),
+            });
+        }
+    }
+}
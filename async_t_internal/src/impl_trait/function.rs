@@ -0,0 +1,37 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{Generics, ItemFn};
+
+use super::{desugar_arguments, lift_signature, take_name, Module, Side};
+use crate::async_t::Backend;
+
+/// lifts the hidden types of a free function into module-level aliases,
+/// named `impl_trait_{function}_{n}` unless `#[name]` is given.
+pub(crate) fn function(mut inner_fn: ItemFn, backend: Backend) -> TokenStream {
+    if backend == Backend::Rpitit {
+        crate::diagnostics::unlifted_name(&mut inner_fn.attrs);
+        desugar_arguments(&mut inner_fn.sig);
+        return quote!(#inner_fn).into();
+    }
+    let module = Module {
+        self_ty: None,
+        generics: Generics::default(),
+        prefix: "impl_trait".into(),
+        vis: inner_fn.vis.clone(),
+    };
+    let name = take_name(&mut inner_fn.attrs);
+    let mut new_types = vec![];
+    let defined = lift_signature(
+        &mut inner_fn.sig,
+        Side::Module(&module),
+        &mut new_types,
+        &[],
+        name,
+    );
+    if !defined.is_empty() {
+        inner_fn
+            .attrs
+            .push(syn::parse_quote!(#[define_opaque(#(#defined),*)]));
+    }
+    quote!(#(#new_types)* #inner_fn).into()
+}
@@ -1,217 +1,186 @@
 use proc_macro::TokenStream;
-use proc_macro_error::abort;
-use quote::{format_ident, quote};
-use syn::punctuated::Punctuated;
-use syn::spanned::Spanned;
-use syn::token::Add;
-use syn::{FnArg, ItemTrait, TraitItem, TraitItemMethod, Type, TypeParam, TypeParamBound};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    parse_quote, GenericArgument, GenericParam, Generics, ItemTrait, TraitItem, TraitItemConst,
+    TraitItemType, Type,
+};
+
+use super::{configure, desugar_arguments, lift_signature, lift_type, take_name, Module, Side};
+use crate::async_t::Backend;
 
 pub(crate) struct TraitDeclaration {
     pub(crate) inner_trait: ItemTrait,
+    pub(crate) backend: Backend,
 }
 
-pub(crate) fn impl_trait(inner_trait: ItemTrait) -> TokenStream {
-    TraitDeclaration::new(inner_trait).process()
+pub(crate) fn impl_trait(inner_trait: ItemTrait, backend: Backend) -> TokenStream {
+    TraitDeclaration::new(inner_trait, backend).process()
 }
 
 impl TraitDeclaration {
-    pub(crate) fn new(inner_trait: ItemTrait) -> Self {
-        Self { inner_trait }
+    pub(crate) fn new(inner_trait: ItemTrait, backend: Backend) -> Self {
+        Self {
+            inner_trait,
+            backend,
+        }
     }
     pub(crate) fn process(self) -> TokenStream {
+        let rpitit = self.backend == Backend::Rpitit;
         let mut t = self.inner_trait;
         let mut new_types = vec![];
-        let trait_lifetimes = t.generics.type_params().collect::<Vec<_>>();
+        let mut module_types = vec![];
+        let module = trait_module(&t);
+        let trait_params = trait_params(&t.generics);
         t.items
             .iter_mut()
             .map(|mut s| match &mut s {
+                // provided methods keep `impl Trait` in return position, since
+                // their body can't name a hidden associated type.
+                // the rpitit backend keeps it in return position for every method.
+                TraitItem::Method(method) if method.default.is_some() || rpitit => {
+                    crate::diagnostics::unlifted_name(&mut method.attrs);
+                    desugar_arguments(&mut method.sig)
+                }
                 TraitItem::Method(method) => {
-                    process_method(method, &mut new_types, &trait_lifetimes)
+                    let name = take_name(&mut method.attrs);
+                    let count = new_types.len();
+                    lift_signature(
+                        &mut method.sig,
+                        Side::Trait,
+                        &mut new_types,
+                        &trait_params,
+                        name,
+                    );
+                    configure(&method.attrs, &mut new_types[count..]);
                 }
-                TraitItem::Verbatim(_)
-                | TraitItem::Const(_)
-                | TraitItem::Macro(_)
-                | TraitItem::Type(_) => (),
-                _ => abort!(s.span(), "please report this bug"),
+                TraitItem::Const(item) => process_const(
+                    item,
+                    &mut new_types,
+                    &mut module_types,
+                    &module,
+                    &trait_params,
+                ),
+                TraitItem::Type(item) => process_assoc_type(item, &mut new_types, &trait_params),
+                TraitItem::Verbatim(_) | TraitItem::Macro(_) => (),
+                item => crate::diagnostics::unsupported_item(item),
             })
             .for_each(drop);
-        let mut new_types = new_types
-            .into_iter()
-            .map(|s| TraitItem::Verbatim(s.into()))
-            .collect();
+        let mut new_types = new_types.into_iter().map(TraitItem::Verbatim).collect();
         t.items.append(&mut new_types);
 
-        quote!(#t).into()
-    }
-}
-
-fn process_method<'a>(
-    method: &'a mut TraitItemMethod,
-    new_types: &'a mut Vec<TokenStream>,
-    trait_lifetimes: &'a [&'a TypeParam],
-) {
-    let mut register = MethodRegister::new(method, new_types, 0, trait_lifetimes);
-    if let syn::ReturnType::Type(arr, mut ty) = method.sig.output.clone() {
-        process_type(&mut ty, &mut register);
-        method.sig.output = syn::ReturnType::Type(arr, ty);
+        quote!(#(#module_types)* #t).into()
     }
 }
 
-fn process_type(ty: &mut Type, register: &mut MethodRegister) {
-    match ty {
-        Type::Array(arr) => process_type(&mut arr.elem, register),
-        Type::Group(group) => process_type(&mut group.elem, register),
-        Type::Paren(paren) => process_type(&mut paren.elem, register),
-        Type::Ptr(ptr) => process_type(&mut ptr.elem, register),
-        Type::Reference(ptr) => process_type(&mut ptr.elem, register),
-        Type::Slice(slice) => process_type(&mut slice.elem, register),
-
-        Type::BareFn(func) => {
-            func.inputs
-                .iter_mut()
-                .for_each(|s| process_type(&mut s.ty, register));
-            if let syn::ReturnType::Type(_, ty) = &mut func.output {
-                process_type(ty, register)
+/// the type and lifetime params of a trait, which must outlive the lifetimes of its hidden types.
+/// const params have no lifetime, so they're left out.
+pub(crate) fn trait_params(generics: &Generics) -> Vec<GenericArgument> {
+    generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                Some(syn::parse2(quote!(#ident)).unwrap())
             }
-        }
-        Type::Path(path) => {
-            path.path
-                .segments
-                .iter_mut()
-                .for_each(|path| match &mut path.arguments {
-                    syn::PathArguments::AngleBracketed(bracketed) => {
-                        bracketed.args.iter_mut().for_each(|arg| match arg {
-                            syn::GenericArgument::Type(ty) => process_type(ty, register),
-                            syn::GenericArgument::Binding(binding) => {
-                                process_type(&mut binding.ty, register)
-                            }
-                            _ => (),
-                        })
-                    }
-                    syn::PathArguments::Parenthesized(paren) => {
-                        paren
-                            .inputs
-                            .iter_mut()
-                            .for_each(|ty| process_type(ty, register));
-                        if let syn::ReturnType::Type(_, ty) = &mut paren.output {
-                            process_type(ty, register)
-                        }
-                    }
-                    syn::PathArguments::None => (),
-                })
-        }
-        Type::Tuple(tuple) => tuple
-            .elems
-            .iter_mut()
-            .for_each(|ty| process_type(ty, register)),
-
-        Type::ImplTrait(tr) => {
-            tr.bounds.iter_mut().for_each(|s| {
-                if let TypeParamBound::Trait(s) = s {
-                    s.path
-                        .segments
-                        .iter_mut()
-                        .for_each(|path| match &mut path.arguments {
-                            syn::PathArguments::AngleBracketed(bracketed) => {
-                                bracketed.args.iter_mut().for_each(|arg| match arg {
-                                    syn::GenericArgument::Type(ty) => process_type(ty, register),
-                                    syn::GenericArgument::Binding(binding) => {
-                                        process_type(&mut binding.ty, register)
-                                    }
-                                    _ => (),
-                                })
-                            }
-                            syn::PathArguments::Parenthesized(paren) => {
-                                paren
-                                    .inputs
-                                    .iter_mut()
-                                    .for_each(|ty| process_type(ty, register));
-                                if let syn::ReturnType::Type(_, ty) = &mut paren.output {
-                                    process_type(ty, register)
-                                }
-                            }
-                            syn::PathArguments::None => (),
-                        })
-                }
-            });
-            *ty = register.register(&tr.bounds);
-        }
-        Type::Never(_)
-        | Type::Verbatim(_)
-        | Type::Macro(_)
-        | Type::TraitObject(_)
-        | Type::Infer(_) => (), // these types don't encapsulate any other type.
-        _ => abort!(ty.span(), "please report this bug"),
-    }
+            GenericParam::Lifetime(param) => {
+                Some(GenericArgument::Lifetime(param.lifetime.clone()))
+            }
+            GenericParam::Const(_) => None,
+        })
+        .collect()
 }
 
-struct MethodRegister<'a> {
-    method: &'a TraitItemMethod,
-    new_types: &'a mut Vec<TokenStream>,
-    counter: u64,
-    types: &'a [&'a TypeParam],
+/// a constant with a default value has the hidden types of the value, which can't be associated
+/// types, since implementations inheriting the value couldn't choose them.
+/// they're lifted into module-level aliases instead, generic over the implementor,
+/// which the default defines.
+fn process_const(
+    item: &mut TraitItemConst,
+    new_types: &mut Vec<TokenStream2>,
+    module_types: &mut Vec<TokenStream2>,
+    module: &Module,
+    types: &[GenericArgument],
+) {
+    let generics = Generics::default();
+    if item.default.is_none() {
+        lift_type(
+            &mut item.ty,
+            &item.ident,
+            &generics,
+            Side::Trait,
+            new_types,
+            types,
+        );
+        return;
+    }
+    let defined = lift_type(
+        &mut item.ty,
+        &item.ident,
+        &generics,
+        Side::Module(module),
+        module_types,
+        types,
+    );
+    if !defined.is_empty() {
+        ImplementorSelf.visit_type_mut(&mut item.ty);
+        item.attrs
+            .push(parse_quote!(#[define_opaque(#(#defined),*)]));
+    }
 }
 
-impl<'a> MethodRegister<'a> {
-    fn new(
-        method: &'a TraitItemMethod,
-        new_types: &'a mut Vec<TokenStream>,
-        counter: u64,
-        types: &'a [&'a TypeParam],
-    ) -> Self {
-        Self {
-            method,
+/// the default of an associated type, which requires `associated_type_defaults`.
+/// implementations restate it with `#[provided]` to choose its hidden types.
+fn process_assoc_type(
+    item: &mut TraitItemType,
+    new_types: &mut Vec<TokenStream2>,
+    types: &[GenericArgument],
+) {
+    if let Some((_, ty)) = &mut item.default {
+        lift_type(
+            ty,
+            &item.ident,
+            &item.generics,
+            Side::Trait,
             new_types,
-            counter,
             types,
-        }
+        );
+    }
+}
+
+/// the module-level aliases of a trait, whose first type param stands in for `Self`.
+fn trait_module(inner_trait: &ItemTrait) -> Module {
+    let ident = &inner_trait.ident;
+    let (_, ty_generics, _) = inner_trait.generics.split_for_impl();
+    let mut generics = inner_trait.generics.clone();
+    let lifetimes = generics.lifetimes().count();
+    generics.params.insert(
+        lifetimes,
+        parse_quote!(__AsyncTImplementor: ?Sized + #ident #ty_generics),
+    );
+    Module {
+        self_ty: Some(parse_quote!(__AsyncTImplementor)),
+        generics,
+        prefix: format!("impl_trait_{}", ident.unraw()),
+        vis: inner_trait.vis.clone(),
     }
+}
 
-    fn register(&mut self, bounds: &Punctuated<TypeParamBound, Add>) -> Type {
-        let ident = &self.method.sig.ident;
-        let mut where_clause = self.method.sig.generics.clone();
-        let where_clause = where_clause.make_where_clause();
+/// names the aliases of [`trait_module`] for `Self` within the trait.
+struct ImplementorSelf;
 
-        let mut extra_bounds = vec![];
-        self.types.clone().iter().for_each(|s| {
-            for lt in self.method.sig.generics.lifetimes() {
-                let ident = &s.ident;
-                extra_bounds.push(syn::parse2(quote!(#ident: #lt)).unwrap());
+impl VisitMut for ImplementorSelf {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Path(path)
+                if path.qself.is_none() && path.path.is_ident("__AsyncTImplementor") =>
+            {
+                *ty = parse_quote!(Self)
             }
-        });
-        for bound in extra_bounds {
-            where_clause.predicates.push(bound);
+            ty => visit_mut::visit_type_mut(self, ty),
         }
-
-        // check for self lifetimes
-        self.method.sig.inputs.first().and_then(|arg| {
-            if let FnArg::Receiver(receiver) = arg {
-                receiver.reference.as_ref().and_then(|(_, lt)| {
-                    lt.as_ref().and_then(|lt| {
-                        where_clause
-                            .predicates
-                            .push(syn::parse2(quote!(Self: #lt)).unwrap());
-                        Some(())
-                    });
-                    Some(())
-                });
-                Some(())
-            } else {
-                Some(())
-            }
-        });
-
-        let (bound_generics, generics, _) = &self.method.sig.generics.split_for_impl();
-        let num = self.counter;
-        let ident = format_ident!("impl_trait_{}_{}", ident, num);
-        let ts = quote!(
-            #[allow(non_camel_case_types)]
-            type #ident #bound_generics: #bounds #where_clause;
-        )
-        .into();
-        self.counter += 1;
-        self.new_types.push(ts);
-        let ty = Type::Path(syn::parse2(quote!(Self::#ident #generics)).unwrap());
-        ty
     }
 }
@@ -0,0 +1,68 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_quote, ImplItem, ItemImpl, Type, Visibility};
+
+use super::{desugar_arguments, lift_signature, take_name, trait_params, Module, Side};
+use crate::async_t::Backend;
+
+/// inherent implementations can't have associated types, so hidden types are lifted into
+/// module-level aliases instead, which the methods defining them name in `#[define_opaque]`.
+pub(crate) fn inherent_implementation(mut inner_impl: ItemImpl, backend: Backend) -> TokenStream {
+    let rpitit = backend == Backend::Rpitit;
+    let mut module = Module {
+        self_ty: Some((*inner_impl.self_ty).clone()),
+        generics: inner_impl.generics.clone(),
+        prefix: prefix(&inner_impl.self_ty),
+        vis: Visibility::Inherited,
+    };
+    let types = trait_params(&inner_impl.generics);
+    let mut new_types = vec![];
+    inner_impl.items.iter_mut().for_each(|item| match item {
+        ImplItem::Method(method) if rpitit => {
+            crate::diagnostics::unlifted_name(&mut method.attrs);
+            desugar_arguments(&mut method.sig);
+        }
+        ImplItem::Method(method) => {
+            let name = take_name(&mut method.attrs);
+            module.vis = method.vis.clone();
+            let defined = lift_signature(
+                &mut method.sig,
+                Side::Module(&module),
+                &mut new_types,
+                &types,
+                name,
+            );
+            if !defined.is_empty() {
+                method
+                    .attrs
+                    .push(parse_quote!(#[define_opaque(#(#defined),*)]));
+            }
+        }
+        ImplItem::Verbatim(_) | ImplItem::Const(_) | ImplItem::Macro(_) | ImplItem::Type(_) => (),
+        item => crate::diagnostics::unsupported_item(item),
+    });
+    quote!(#(#new_types)* #inner_impl).into()
+}
+
+/// `impl_trait_{Type}_{hash}`, where the hash of the implemented type tells apart
+/// implementations of the same type with different generic arguments.
+fn prefix(self_ty: &Type) -> String {
+    let ident = match self_ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    };
+    let mut hasher = DefaultHasher::new();
+    quote!(#self_ty).to_string().hash(&mut hasher);
+    format!(
+        "impl_trait_{}_{:08x}",
+        ident.as_deref().unwrap_or("Self"),
+        hasher.finish() as u32
+    )
+}
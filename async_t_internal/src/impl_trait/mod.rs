@@ -1,5 +1,16 @@
+mod arguments;
+mod function;
+#[allow(clippy::module_inception)]
 mod impl_trait;
 mod implementation;
+mod inherent;
+mod receiver;
+mod visitor;
 
+pub(crate) use arguments::*;
+pub(crate) use function::*;
 pub(crate) use impl_trait::*;
 pub(crate) use implementation::*;
+pub(crate) use inherent::*;
+pub(crate) use receiver::*;
+pub(crate) use visitor::*;
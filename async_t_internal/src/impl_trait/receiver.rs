@@ -0,0 +1,153 @@
+use proc_macro2::Span;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    FnArg, GenericArgument, GenericParam, Lifetime, LifetimeDef, ParenthesizedGenericArguments,
+    Pat, PathArguments, ReturnType, Signature, Type, TypeBareFn, TypePath, TypeReference,
+};
+
+/// the lifetime of the reference to `Self` in the receiver, which elided output lifetimes get.
+/// besides `&self`, this is found in typed receivers such as `self: Pin<&mut Self>`.
+pub(crate) fn self_lifetime(sig: &mut Signature) -> Option<&mut Option<Lifetime>> {
+    match sig.inputs.first_mut()? {
+        FnArg::Receiver(receiver) => receiver.reference.as_mut().map(|(_, lt)| lt),
+        FnArg::Typed(arg) => match &*arg.pat {
+            Pat::Ident(pat) if pat.ident == "self" => self_reference(&mut arg.ty),
+            _ => None,
+        },
+    }
+}
+
+fn self_reference(ty: &mut Type) -> Option<&mut Option<Lifetime>> {
+    match ty {
+        Type::Reference(reference) => {
+            if is_self(&reference.elem) {
+                Some(&mut reference.lifetime)
+            } else {
+                self_reference(&mut reference.elem)
+            }
+        }
+        Type::Group(group) => self_reference(&mut group.elem),
+        Type::Paren(paren) => self_reference(&mut paren.elem),
+        Type::Path(path) if path.qself.is_none() => {
+            path.path
+                .segments
+                .iter_mut()
+                .find_map(|segment| match &mut segment.arguments {
+                    PathArguments::AngleBracketed(args) => {
+                        args.args.iter_mut().find_map(|arg| match arg {
+                            GenericArgument::Type(ty) => self_reference(ty),
+                            _ => None,
+                        })
+                    }
+                    _ => None,
+                })
+        }
+        _ => None,
+    }
+}
+
+fn is_self(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident("Self"))
+}
+
+/// names an elided lifetime of the receiver `'impl_trait_self` if the output borrows from it,
+/// giving it to every elided lifetime of the output, since hidden types can't elide them.
+/// returns the lifetime of the receiver, if it's named.
+pub(crate) fn name_receiver(sig: &mut Signature) -> Option<Lifetime> {
+    match self_lifetime(sig)? {
+        Some(lifetime) if lifetime.ident != "_" => return Some(lifetime.clone()),
+        _ => (),
+    }
+    let lifetime = Lifetime::new("'impl_trait_self", Span::call_site());
+    let mut resolve = Elision::new(ResolveElided::new(lifetime.clone()));
+    if let ReturnType::Type(_, ty) = &mut sig.output {
+        resolve.visit_type_mut(ty);
+    }
+    if !resolve.elide.resolved {
+        return None;
+    }
+    *self_lifetime(sig)? = Some(lifetime.clone());
+    sig.generics
+        .params
+        .push(GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())));
+    Some(lifetime)
+}
+
+/// what [`Elision`] does with the lifetimes it visits.
+pub(crate) trait Elide {
+    /// the lifetime an elided one becomes.
+    fn elided(&mut self) -> Lifetime;
+
+    /// every lifetime after it's named, along with those of the references it's behind.
+    fn lifetime(&mut self, _lifetime: &Lifetime, _references: &[Lifetime]) {}
+
+    /// every type path, along with the lifetimes of the references it's behind.
+    fn type_path(&mut self, _path: &TypePath, _references: &[Lifetime]) {}
+}
+
+/// visits the elided lifetimes of a type, i.e. those of references and `'_`.
+/// lifetimes of function pointers and `Fn` bounds are bound by them, so they're left alone.
+pub(crate) struct Elision<E> {
+    pub(crate) elide: E,
+    references: Vec<Lifetime>,
+}
+
+impl<E: Elide> Elision<E> {
+    pub(crate) fn new(elide: E) -> Self {
+        Self {
+            elide,
+            references: vec![],
+        }
+    }
+}
+
+impl<E: Elide> VisitMut for Elision<E> {
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        let lifetime = reference
+            .lifetime
+            .get_or_insert_with(|| self.elide.elided());
+        self.visit_lifetime_mut(lifetime);
+        self.references.push(lifetime.clone());
+        self.visit_type_mut(&mut reference.elem);
+        self.references.pop();
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime.ident == "_" {
+            *lifetime = self.elide.elided();
+        }
+        self.elide.lifetime(lifetime, &self.references);
+    }
+
+    fn visit_type_path_mut(&mut self, path: &mut TypePath) {
+        self.elide.type_path(path, &self.references);
+        visit_mut::visit_type_path_mut(self, path);
+    }
+
+    fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+
+    fn visit_parenthesized_generic_arguments_mut(&mut self, _: &mut ParenthesizedGenericArguments) {
+    }
+}
+
+/// gives every elided lifetime of an output the lifetime elision picked.
+pub(crate) struct ResolveElided {
+    lifetime: Lifetime,
+    resolved: bool,
+}
+
+impl ResolveElided {
+    pub(crate) fn new(lifetime: Lifetime) -> Self {
+        Self {
+            lifetime,
+            resolved: false,
+        }
+    }
+}
+
+impl Elide for ResolveElided {
+    fn elided(&mut self) -> Lifetime {
+        self.resolved = true;
+        self.lifetime.clone()
+    }
+}
@@ -0,0 +1,323 @@
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_error::emit_error;
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Add;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    Attribute, GenericArgument, GenericParam, Generics, Ident, Lifetime, Lit, Meta, MetaNameValue,
+    ReturnType, Signature, Type, TypeImplTrait, TypeParamBound, Visibility,
+};
+
+use super::{desugar_arguments, name_receiver};
+
+/// whether hidden types are declared for a trait, defined for an implementation,
+/// or defined in the module, for inherent implementations and functions,
+/// which can't have associated types.
+#[derive(Clone, Copy)]
+pub(crate) enum Side<'a> {
+    Trait,
+    Impl,
+    Module(&'a Module),
+}
+
+/// the inherent implementation or function hidden types are lifted out of, into module-level aliases.
+pub(crate) struct Module {
+    /// the type being implemented, which stands in for `Self` outside the implementation.
+    /// functions have no `Self`.
+    pub(crate) self_ty: Option<Type>,
+    /// the generics of the implementation, which come before those of the method.
+    pub(crate) generics: Generics,
+    /// names the aliases, as they share the module with those of other implementations.
+    pub(crate) prefix: String,
+    /// the visibility of the method being lifted, so callers outside the module can name its output.
+    pub(crate) vis: Visibility,
+}
+
+/// gives the hidden types of an item the `#[cfg]`s of the item,
+/// so items behind opposite `#[cfg]`s can share a name.
+pub(crate) fn configure(attrs: &[Attribute], new_types: &mut [TokenStream2]) {
+    let cfgs = attrs.iter().filter(|s| s.path.is_ident("cfg"));
+    new_types.iter_mut().for_each(|ty| {
+        let cfgs = cfgs.clone();
+        *ty = quote!(#(#cfgs)* #ty);
+    });
+}
+
+/// takes `#[name = "TypeName"]` off a method, which names the hidden type of its output.
+pub(crate) fn take_name(attrs: &mut Vec<Attribute>) -> Option<Ident> {
+    let index = attrs.iter().position(|s| s.path.is_ident("name"))?;
+    let attr = attrs.remove(index);
+    match attr.parse_meta() {
+        Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(lit), ..
+        })) => match lit.parse::<Ident>() {
+            Ok(ident) => Some(ident),
+            Err(_) => {
+                emit_error!(lit.span(), "`{}` isn't a valid type name", lit.value());
+                None
+            }
+        },
+        _ => {
+            emit_error!(attr.span(), "expected `#[name = \"TypeName\"]`");
+            None
+        }
+    }
+}
+
+/// desugars the arguments of a method and lifts every `impl Trait` of its where clause and output,
+/// returning the names of the hidden types.
+/// `name` replaces the generated name of the hidden type of an `impl Trait` output.
+pub(crate) fn lift_signature(
+    sig: &mut Signature,
+    side: Side,
+    new_types: &mut Vec<TokenStream2>,
+    types: &[GenericArgument],
+    name: Option<Ident>,
+) -> Vec<Ident> {
+    desugar_arguments(sig);
+    let receiver = name_receiver(sig);
+    // hidden types of the where clause can't be bound by the clause they're lifted out of,
+    // and only take the lifetimes they name, so they can be constrained in an async body.
+    let mut unbounded = sig.generics.clone();
+    unbounded.where_clause = None;
+    if let Some(where_clause) = &sig.generics.where_clause {
+        let mut named = ImplLifetimes::default();
+        named.visit_where_clause_mut(&mut where_clause.clone());
+        unbounded.params = std::mem::take(&mut unbounded.params)
+            .into_iter()
+            .filter(|param| match param {
+                GenericParam::Lifetime(lt) => named.lifetimes.contains(&lt.lifetime),
+                _ => true,
+            })
+            .collect();
+    }
+    let mut register = Register::new(side, &sig.ident, &unbounded, new_types, types);
+    if let Some(where_clause) = &mut sig.generics.where_clause {
+        register.visit_where_clause_mut(where_clause);
+    }
+    register.generics = merge_generics(side, &sig.generics);
+    register.receiver = receiver;
+    let ty = match &mut sig.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => {
+            if let Some(name) = name {
+                emit_error!(
+                    name.span(),
+                    "`#[name]` requires the return type to be `impl Trait`"
+                );
+            }
+            return register.defined;
+        }
+    };
+    match (name, &mut **ty) {
+        (Some(name), Type::ImplTrait(tr)) => {
+            visit_mut::visit_type_impl_trait_mut(&mut register, tr);
+            let named = register.register(&tr.bounds, Some(name));
+            **ty = named;
+        }
+        (name, ty) => {
+            if let Some(name) = name {
+                emit_error!(
+                    name.span(),
+                    "`#[name]` requires the return type to be `impl Trait`"
+                );
+            }
+            register.visit_type_mut(ty);
+        }
+    }
+    register.defined
+}
+
+/// lifts every `impl Trait` of the type of an associated const or type,
+/// returning the names of the hidden types.
+pub(crate) fn lift_type(
+    ty: &mut Type,
+    ident: &Ident,
+    generics: &Generics,
+    side: Side,
+    new_types: &mut Vec<TokenStream2>,
+    types: &[GenericArgument],
+) -> Vec<Ident> {
+    let mut register = Register::new(side, ident, generics, new_types, types);
+    register.visit_type_mut(ty);
+    register.defined
+}
+
+/// registers the hidden types of a single trait item, which are named after it.
+struct Register<'a> {
+    side: Side<'a>,
+    ident: &'a Ident,
+    generics: Generics,
+    receiver: Option<Lifetime>,
+    new_types: &'a mut Vec<TokenStream2>,
+    counter: u64,
+    types: &'a [GenericArgument],
+    defined: Vec<Ident>,
+}
+
+impl<'a> Register<'a> {
+    fn new(
+        side: Side<'a>,
+        ident: &'a Ident,
+        generics: &Generics,
+        new_types: &'a mut Vec<TokenStream2>,
+        types: &'a [GenericArgument],
+    ) -> Self {
+        Self {
+            side,
+            ident,
+            generics: merge_generics(side, generics),
+            receiver: None,
+            new_types,
+            counter: 0,
+            types,
+            defined: vec![],
+        }
+    }
+
+    fn register(&mut self, bounds: &Punctuated<TypeParamBound, Add>, name: Option<Ident>) -> Type {
+        let mut where_clause = self.generics.clone();
+        let where_clause = where_clause.make_where_clause();
+        let self_ty = match self.side {
+            Side::Module(module) => module.self_ty.clone(),
+            Side::Trait | Side::Impl => Some(syn::parse2(quote!(Self)).unwrap()),
+        };
+
+        // `Self` and every type and lifetime param of the trait must outlive the method lifetimes.
+        // bounding `Self` rather than the impl's own params keeps blanket impls in line with the trait.
+        for lt in self.generics.lifetimes() {
+            let lt = &lt.lifetime;
+            if let Some(self_ty) = &self_ty {
+                where_clause
+                    .predicates
+                    .push(syn::parse2(quote!(#self_ty: #lt)).unwrap());
+            }
+            for ty in self.types {
+                where_clause
+                    .predicates
+                    .push(syn::parse2(quote!(#ty: #lt)).unwrap());
+            }
+        }
+
+        // check for self lifetimes
+        if let Some(lt) = &self.receiver {
+            if let Some(self_ty) = &self_ty {
+                where_clause
+                    .predicates
+                    .push(syn::parse2(quote!(#self_ty: #lt)).unwrap());
+            }
+        }
+
+        let (bound_generics, generics, _) = self.generics.split_for_impl();
+        let ident = name.unwrap_or_else(|| {
+            self.counter += 1;
+            match self.side {
+                Side::Module(module) => {
+                    format_ident!("{}_{}_{}", module.prefix, self.ident, self.counter - 1)
+                }
+                Side::Trait | Side::Impl => {
+                    format_ident!("impl_trait_{}_{}", self.ident, self.counter - 1)
+                }
+            }
+        });
+        self.defined.push(ident.clone());
+        self.new_types.push(match self.side {
+            Side::Trait => quote!(
+                #[allow(non_camel_case_types)]
+                type #ident #bound_generics: #bounds #where_clause;
+            ),
+            Side::Impl => quote!(
+                #[allow(non_camel_case_types)]
+                type #ident #bound_generics = impl #bounds #where_clause;
+            ),
+            Side::Module(module) => {
+                let vis = &module.vis;
+                let mut bounds = bounds.clone();
+                let mut where_clause = where_clause.clone();
+                if let Some(self_ty) = &module.self_ty {
+                    bounds
+                        .iter_mut()
+                        .for_each(|bound| ReplaceSelf(self_ty).visit_type_param_bound_mut(bound));
+                    ReplaceSelf(self_ty).visit_where_clause_mut(&mut where_clause);
+                }
+                quote!(
+                    #[doc(hidden)]
+                    #[allow(non_camel_case_types)]
+                    #vis type #ident #bound_generics #where_clause = impl #bounds;
+                )
+            }
+        });
+        match self.side {
+            Side::Module(_) => Type::Path(syn::parse2(quote!(#ident #generics)).unwrap()),
+            Side::Trait | Side::Impl => {
+                Type::Path(syn::parse2(quote!(Self::#ident #generics)).unwrap())
+            }
+        }
+    }
+}
+
+/// the generics of the hidden types of an item, which come after those of the inherent
+/// implementation it's lifted out of.
+fn merge_generics(side: Side, generics: &Generics) -> Generics {
+    match side {
+        Side::Module(module) => {
+            let mut merged = module.generics.clone();
+            merged.params.extend(generics.params.iter().cloned());
+            if let Some(where_clause) = &generics.where_clause {
+                merged
+                    .make_where_clause()
+                    .predicates
+                    .extend(where_clause.predicates.iter().cloned());
+            }
+            merged
+        }
+        Side::Trait | Side::Impl => generics.clone(),
+    }
+}
+
+impl VisitMut for Register<'_> {
+    /// nested `impl Trait` is lifted first, so the outer hidden type can name the inner ones.
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        visit_mut::visit_type_mut(self, ty);
+        if let Type::ImplTrait(tr) = ty {
+            *ty = self.register(&tr.bounds, None);
+        }
+    }
+}
+
+/// the lifetimes named in the `impl Trait` of a where clause.
+#[derive(Default)]
+struct ImplLifetimes {
+    nested: bool,
+    lifetimes: Vec<Lifetime>,
+}
+
+impl VisitMut for ImplLifetimes {
+    fn visit_type_impl_trait_mut(&mut self, tr: &mut TypeImplTrait) {
+        let nested = std::mem::replace(&mut self.nested, true);
+        visit_mut::visit_type_impl_trait_mut(self, tr);
+        self.nested = nested;
+    }
+
+    fn visit_lifetime_mut(&mut self, lt: &mut Lifetime) {
+        if self.nested {
+            self.lifetimes.push(lt.clone());
+        }
+    }
+}
+
+/// `Self` can't be used in module-level aliases, so it's replaced by the implemented type.
+struct ReplaceSelf<'a>(&'a Type);
+
+impl VisitMut for ReplaceSelf<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Path(path) if path.qself.is_none() && path.path.is_ident("Self") => {
+                *ty = self.0.clone()
+            }
+            ty => visit_mut::visit_type_mut(self, ty),
+        }
+    }
+}
@@ -1,21 +1,56 @@
 mod async_t;
+mod diagnostics;
 mod impl_trait;
 
+use diagnostics::Target;
 use proc_macro::TokenStream;
-use syn::{ItemImpl, ItemTrait};
+use proc_macro_error::proc_macro_error;
 
-/// requires nightly and cannot be used with dynamic dispatch.
-/// also has limited support for generics.
+/// the default `static` backend requires nightly, while `boxed` and `rpitit` work on stable,
+/// and `#[async_trait(dyn)]` adds a companion trait for dynamic dispatch.
+/// traits may have type, lifetime and const params, e.g. `trait Store<'a, T, const N: usize>`.
 /// | it doesn't use any dynamic dispatch and is a complete zero cost wrapper.
 /// | requires features [ generic_associated_types, type_alias_impl_trait ]
+/// | `#[async_trait(dyn)]` on a trait also generates an object safe `Dyn{Trait}` companion
+/// | with boxed futures, implemented for every implementor of the trait.
+/// | the future captures every input lifetime, so `async fn get(&self, key: &str) -> Option<&V>`
+/// | needs no annotations.
+/// | arguments of type `impl Trait` become generic params of the method, which the future captures,
+/// | so explicit generic args take a trailing `_` for each, e.g. `x.run::<u8, _>(..)`.
+/// | `#[async_trait(?Send)]` makes every future unsend unless the method is marked `#[send]`,
+/// | and must be given to both the trait and its implementations.
+/// | `Send` futures require `Self: Sync` for `&self` and `Self: Send` otherwise, which is added
+/// | to the method along with `Send` bounds on its type params, unless it's `#[no_send_bounds]`.
+/// | every required async method also gets a `{Trait}{Method}Future` alias to its future,
+/// | e.g. `SpawnSpawnFuture<'a, T>` for `Spawn::spawn`, which generic code can store and bound on.
+/// | `future!(T)` is a future returning `T`, which is `Send` unless the method is unsend,
+/// | e.g. `fn handler(&self) -> impl Fn(u8) -> future!(u8)`, where `#[send]` and `#[unsend]` apply too.
+/// | the trait also gets a `{Trait}Send` companion for implementors whose futures are all `Send`.
+/// | `#[async_trait(boxed)]` returns `Pin<Box<dyn Future>>` instead, which works on stable and
+/// | keeps the trait object safe. `#[async_trait(rpitit)]` returns `impl Future`, which also
+/// | works on stable but isn't object safe. `#[async_trait(static)]` selects the zero cost backend,
+/// | which is the default unless the `boxed` or `rpitit` feature is enabled.
+/// | `#[async_trait(crate = path)]` gives the path to `async_t`, if it isn't a direct dependency
+/// | under that name.
 #[proc_macro_attribute]
-pub fn async_trait(_: TokenStream, tokens: TokenStream) -> TokenStream {
-    match syn::parse::<ItemTrait>(tokens.clone()) {
-        Ok(inner_trait) => async_t::trait_implementation(inner_trait),
-        Err(_) => {
-            let inner_trait = syn::parse::<ItemImpl>(tokens).unwrap();
-            async_t::implementation(inner_trait)
+#[proc_macro_error]
+pub fn async_trait(args: TokenStream, tokens: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as async_t::Args);
+    match diagnostics::parse_target("async_trait", tokens, false) {
+        Target::Trait(mut inner_trait) => {
+            async_t::trait_async_types(&mut inner_trait, &args);
+            let dyn_trait = args
+                .dynamic
+                .then(|| async_t::dyn_trait(&inner_trait, &args));
+            let mut ts = async_t::trait_implementation(inner_trait, &args);
+            ts.extend(dyn_trait);
+            ts
         }
+        Target::Impl(mut inner_impl) => {
+            async_t::impl_async_types(&mut inner_impl, &args);
+            async_t::implementation(inner_impl, &args)
+        }
+        Target::Fn(_) => unreachable!("#[async_trait] doesn't accept functions"),
     }
 }
 
@@ -30,19 +65,35 @@ pub fn async_trait(_: TokenStream, tokens: TokenStream) -> TokenStream {
 ///         impl Display, // supports using `impl Trait` as a first-class type
 ///         Result<impl AllTraitsSupported, impl Iterator<Item = impl IsOk>>,
 ///         [impl Display; 30],
-///         fn(impl AnyTrait) -> impl Any
+///         fn(impl AnyTrait) -> impl Any,
+///         Box<dyn Iterator<Item = impl Display>>
 ///     );
+///     // `impl Trait` nested in callback arguments becomes a generic parameter
+///     fn b(&self, cb: impl Fn() -> impl Display);
+///     // as do the types of associated constants and the defaults of associated types
+///     const C: impl Display;
+///     type T = Vec<impl Display>;
+///     // and in where clauses, where `Item = impl Display` becomes `Item: Display`
+///     fn d<I>(&self, i: I) where I: IntoIterator<Item = impl Display>, I: Into<impl Display>;
 /// }
 /// ```
+/// | elided lifetimes of the output, such as `impl Display + '_`, borrow from the receiver,
+/// | which may also be typed, e.g. `self: Pin<&mut Self>`.
+/// | `#[name = "TypeName"]` on a method returning `impl Trait` names its hidden type,
+/// | which otherwise is `impl_trait_{method}_{n}`. it has to be given to the implementations as well.
+/// | `#[impl_trait(rpitit)]` keeps `impl Trait` in return position instead of lifting it
+/// | into hidden associated types, which works on stable. it's the default with the `rpitit` feature.
+/// | on inherent implementations hidden types are lifted into module-level aliases instead,
+/// | which requires the `type_alias_impl_trait` feature.
+/// | free functions are supported the same way, e.g. `#[impl_trait] fn f() -> [impl Display; 3]`.
 #[proc_macro_attribute]
-pub fn impl_trait(_: TokenStream, tokens: TokenStream) -> TokenStream {
-    let ts = match syn::parse::<ItemTrait>(tokens.clone()) {
-        Ok(inner_trait) => impl_trait::impl_trait(inner_trait),
-        Err(_) => {
-            let inner_trait = syn::parse::<ItemImpl>(tokens).unwrap();
-            impl_trait::trait_implementation(inner_trait)
-        }
-    };
-
-    ts.into()
+#[proc_macro_error]
+pub fn impl_trait(args: TokenStream, tokens: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as async_t::Args);
+    let backend = diagnostics::impl_trait_backend(&args);
+    match diagnostics::parse_target("impl_trait", tokens, true) {
+        Target::Trait(inner_trait) => impl_trait::impl_trait(inner_trait, backend),
+        Target::Impl(inner_impl) => impl_trait::trait_implementation(inner_impl, backend),
+        Target::Fn(inner_fn) => impl_trait::function(inner_fn, backend),
+    }
 }